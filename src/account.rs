@@ -0,0 +1,48 @@
+/// Social media platforms recorded for `People`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Twitter,
+    Facebook,
+    Youtube,
+}
+
+impl Platform {
+    pub fn parse(name: &str) -> Option<Platform> {
+        [Platform::Twitter, Platform::Facebook, Platform::Youtube]
+            .into_iter()
+            .find(|p| p.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Twitter => "twitter",
+            Platform::Facebook => "facebook",
+            Platform::Youtube => "youtube",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account<'a> {
+    pub platform: Platform,
+    /// Handle or URL used to deduplicate accounts of one person.
+    pub account: String,
+    pub url: Option<&'a str>,
+    pub screenshot: Option<&'a str>,
+}
+
+/// Reduce `@Handle`, `twitter.com/Handle` or `https://x.com/Handle/` to `handle`.
+pub fn twitter_handle(raw: &str) -> String {
+    let raw = raw.trim().trim_end_matches('/');
+    let raw = match raw.rfind("twitter.com/").or_else(|| raw.rfind("x.com/")) {
+        Some(i) => raw[i..].split_once('/').map_or(raw, |(_, handle)| handle),
+        None => raw,
+    };
+    let raw = raw.split(['/', '?', '#']).next().unwrap_or(raw);
+    raw.trim_start_matches('@').to_lowercase()
+}
+
+/// Trim whitespace and trailing slashes so the same URL dedups.
+pub fn url_account(raw: &str) -> String {
+    raw.trim().trim_end_matches('/').to_string()
+}
@@ -0,0 +1,311 @@
+use clap::ValueEnum;
+use serde_json::json;
+use sqlx::{Pool, Postgres, Row, Transaction};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Entity {
+    Source,
+    People,
+}
+
+/// A table pointing at an entity, with the columns that make its rows unique
+/// besides the entity id. An empty key means rows never collide.
+struct Link {
+    table: &'static str,
+    column: &'static str,
+    key: &'static [&'static str],
+}
+
+impl Entity {
+    fn table(&self) -> &'static str {
+        match self {
+            Entity::Source => "source",
+            Entity::People => "people",
+        }
+    }
+
+    fn alias_table(&self) -> (&'static str, &'static str) {
+        match self {
+            Entity::Source => ("source_alias", "source_id"),
+            Entity::People => ("people_alias", "people_id"),
+        }
+    }
+
+    fn links(&self) -> &'static [Link] {
+        match self {
+            Entity::Source => &[
+                Link { table: "source_article", column: "source_id", key: &["article_id"] },
+                Link { table: "source_affiliation", column: "source_id", key: &["kind", "value"] },
+                Link { table: "source_alias", column: "source_id", key: &["alias"] },
+            ],
+            Entity::People => &[
+                Link { table: "opinion", column: "author_id", key: &[] },
+                Link { table: "people_identity", column: "people_id", key: &["identity_id", "value"] },
+                Link { table: "people_account", column: "people_id", key: &["platform", "account"] },
+                Link {
+                    table: "legislator_term",
+                    column: "people_id",
+                    key: &["chamber", "state", "district", "party", "period"],
+                },
+                Link { table: "people_alias", column: "people_id", key: &["alias"] },
+            ],
+        }
+    }
+
+    /// Columns of the entity that a merge fills from the dropped row when the kept one has none.
+    fn attributes(&self) -> &'static [&'static str] {
+        match self {
+            Entity::Source => &["country_id"],
+            Entity::People => &["title", "origin"],
+        }
+    }
+}
+
+/// Move every reference from `drop_id` to `keep_id`, discarding rows the kept
+/// entity already has. Returns how many rows were moved and discarded.
+async fn repoint(tx: &mut Transaction<'_, Postgres>, link: &Link, keep_id: i32, drop_id: i32) -> Result<(u64, u64)> {
+    let duplicate = if link.key.is_empty() {
+        String::new()
+    } else {
+        let same = link
+            .key
+            .iter()
+            .map(|c| format!("k.{c} IS NOT DISTINCT FROM t.{c}"))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        format!(
+            " AND NOT EXISTS (SELECT 1 FROM {table} k WHERE k.{column} = $1 AND {same})",
+            table = link.table,
+            column = link.column,
+        )
+    };
+    let moved = sqlx::query(&format!(
+        "UPDATE {table} t SET {column} = $1 WHERE {column} = $2{duplicate}",
+        table = link.table,
+        column = link.column,
+    ))
+    .bind(keep_id)
+    .bind(drop_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let dropped = sqlx::query(&format!("DELETE FROM {} WHERE {} = $1", link.table, link.column))
+        .bind(drop_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    Ok((moved, dropped))
+}
+
+async fn audit(
+    tx: &mut Transaction<'_, Postgres>,
+    action: &str,
+    entity: Entity,
+    target_id: i32,
+    other_id: i32,
+    detail: serde_json::Value,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO entity_audit (action, entity, target_id, other_id, detail) \
+        VALUES ($1, $2, $3, $4, $5::JSONB)",
+    )
+    .bind(action)
+    .bind(entity.table())
+    .bind(target_id)
+    .bind(other_id)
+    .bind(detail.to_string())
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+async fn name_of(tx: &mut Transaction<'_, Postgres>, entity: Entity, id: i32) -> Result<String> {
+    Ok(sqlx::query(&format!("SELECT name FROM {} WHERE id = $1 FOR UPDATE", entity.table()))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| format!("No {} with id {}", entity.table(), id))?
+        .get("name"))
+}
+
+/// Merge `drop_id` into `keep_id`, keeping the dropped name as an alias.
+pub async fn merge(pool: &Pool<Postgres>, entity: Entity, keep_id: i32, drop_id: i32) -> Result<()> {
+    if keep_id == drop_id {
+        return Err("Cannot merge a row into itself".into());
+    }
+    let mut tx = pool.begin().await?;
+    name_of(&mut tx, entity, keep_id).await?;
+    let drop_name = name_of(&mut tx, entity, drop_id).await?;
+
+    let mut links = serde_json::Map::new();
+    for link in entity.links() {
+        let (moved, dropped) = repoint(&mut tx, link, keep_id, drop_id).await?;
+        links.insert(link.table.to_string(), json!({ "moved": moved, "duplicates": dropped }));
+    }
+    let fill = entity
+        .attributes()
+        .iter()
+        .map(|c| format!("{c} = COALESCE(k.{c}, d.{c})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    sqlx::query(&format!(
+        "UPDATE {table} k SET {fill} FROM {table} d WHERE k.id = $1 AND d.id = $2",
+        table = entity.table(),
+    ))
+    .bind(keep_id)
+    .bind(drop_id)
+    .execute(&mut tx)
+    .await?;
+    let (alias_table, alias_column) = entity.alias_table();
+    sqlx::query(&format!(
+        "INSERT INTO {alias_table} ({alias_column}, alias) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+    ))
+    .bind(keep_id)
+    .bind(&drop_name)
+    .execute(&mut tx)
+    .await?;
+    sqlx::query(&format!("DELETE FROM {} WHERE id = $1", entity.table()))
+        .bind(drop_id)
+        .execute(&mut tx)
+        .await?;
+
+    audit(&mut tx, "merge", entity, keep_id, drop_id, json!({ "name": drop_name, "links": links })).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Reassign opinions of a person, or article links of a source, from
+/// `from_id` to `to_id` or to a new entity called `name`.
+pub async fn split(
+    pool: &Pool<Postgres>,
+    entity: Entity,
+    from_id: i32,
+    to_id: Option<i32>,
+    name: Option<&str>,
+    ids: &[i32],
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    name_of(&mut tx, entity, from_id).await?;
+    let to_id = match (to_id, name) {
+        (Some(id), None) => {
+            name_of(&mut tx, entity, id).await?;
+            id
+        }
+        (None, Some(name)) => sqlx::query(&format!(
+            "INSERT INTO {table} (name, country_id) SELECT $2, country_id FROM {table} WHERE id = $1 RETURNING id",
+            table = entity.table(),
+        ))
+        .bind(from_id)
+        .bind(name)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| format!("Cannot create {} {:?}, pass --to if it exists: {}", entity.table(), name, e))?
+        .get("id"),
+        _ => return Err("Pass exactly one of --to and --name".into()),
+    };
+    if to_id == from_id {
+        return Err("Cannot split a row into itself".into());
+    }
+
+    let moved = match entity {
+        Entity::People => sqlx::query("UPDATE opinion SET author_id = $1 WHERE author_id = $2 AND id = ANY($3)")
+            .bind(to_id)
+            .bind(from_id)
+            .bind(ids)
+            .execute(&mut tx)
+            .await?
+            .rows_affected(),
+        Entity::Source => {
+            let linked = sqlx::query("SELECT count(*) FROM source_article WHERE source_id = $1 AND article_id = ANY($2)")
+                .bind(from_id)
+                .bind(ids)
+                .fetch_one(&mut tx)
+                .await?
+                .get::<i64, _>(0);
+            sqlx::query(
+                "INSERT INTO source_article (source_id, article_id) \
+                SELECT $1, article_id FROM source_article WHERE source_id = $2 AND article_id = ANY($3) \
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(to_id)
+            .bind(from_id)
+            .bind(ids)
+            .execute(&mut tx)
+            .await?;
+            sqlx::query("DELETE FROM source_article WHERE source_id = $1 AND article_id = ANY($2)")
+                .bind(from_id)
+                .bind(ids)
+                .execute(&mut tx)
+                .await?;
+            linked as u64
+        }
+    };
+    if moved != ids.len() as u64 {
+        return Err(format!("Only {} of {} ids belong to {} {}", moved, ids.len(), entity.table(), from_id).into());
+    }
+
+    audit(&mut tx, "split", entity, to_id, from_id, json!({ "ids": ids })).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    /// Needs a database at `TEST_POSTGRES_URL`, and is skipped without one.
+    #[tokio::test]
+    async fn merge_and_split_check_ids_and_roll_back() {
+        let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+            return;
+        };
+        // One connection, so the search path holds for every statement
+        let pool = PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap();
+        for sql in ["DROP SCHEMA IF EXISTS admin_test CASCADE", "CREATE SCHEMA admin_test", "SET search_path TO admin_test"] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        crate::postgres::create_tables(&pool).await.unwrap();
+        for sql in [
+            "INSERT INTO people (id, name) OVERRIDING SYSTEM VALUE VALUES (1, 'Joe'), (2, 'Joseph')",
+            "INSERT INTO article (id, key) OVERRIDING SYSTEM VALUE VALUES (1, 'a1')",
+            "INSERT INTO opinion (id, author_id, text, article_id) OVERRIDING SYSTEM VALUE \
+            VALUES (1, 1, 'first', 1), (2, 1, 'second', 1)",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        let authors = || async {
+            sqlx::query_as::<_, (i32,)>("SELECT author_id FROM opinion ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(a,)| a)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(merge(&pool, Entity::People, 1, 1).await.is_err());
+        assert!(merge(&pool, Entity::People, 1, 3).await.is_err());
+        assert!(split(&pool, Entity::People, 1, Some(1), None, &[1]).await.is_err());
+        assert!(split(&pool, Entity::People, 1, Some(2), Some("Jo"), &[1]).await.is_err());
+        // An opinion of someone else rolls back the whole split
+        assert!(split(&pool, Entity::People, 2, Some(1), None, &[1]).await.is_err());
+        assert!(split(&pool, Entity::People, 1, Some(2), None, &[1, 3]).await.is_err());
+        assert_eq!(authors().await, [1, 1]);
+
+        split(&pool, Entity::People, 1, Some(2), None, &[1]).await.unwrap();
+        assert_eq!(authors().await, [2, 1]);
+        merge(&pool, Entity::People, 1, 2).await.unwrap();
+        assert_eq!(authors().await, [1, 1]);
+        let aliases: Vec<(i32, String)> = sqlx::query_as("SELECT people_id, alias FROM people_alias")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(aliases, [(1, "Joseph".to_string())]);
+
+        sqlx::query("DROP SCHEMA admin_test CASCADE").execute(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+/// Kinds of affiliation carried by the `From_*` fields of `Source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affiliation {
+    Blog,
+    Bank,
+    Department,
+    Journal,
+    Institution,
+    Senator,
+    DepartmentCountry,
+    EmbassyCountry,
+    EmbassyLocated,
+    Facebook,
+    NewsAgency,
+    Organization,
+    Representatives,
+    RepresentativesRegion,
+    SenatorRegion,
+    Twitter,
+    University,
+    UniversityRegion,
+    UniversityNews,
+    Web,
+}
+
+impl Affiliation {
+    pub const ALL: [Affiliation; 20] = [
+        Affiliation::Blog,
+        Affiliation::Bank,
+        Affiliation::Department,
+        Affiliation::Journal,
+        Affiliation::Institution,
+        Affiliation::Senator,
+        Affiliation::DepartmentCountry,
+        Affiliation::EmbassyCountry,
+        Affiliation::EmbassyLocated,
+        Affiliation::Facebook,
+        Affiliation::NewsAgency,
+        Affiliation::Organization,
+        Affiliation::Representatives,
+        Affiliation::RepresentativesRegion,
+        Affiliation::SenatorRegion,
+        Affiliation::Twitter,
+        Affiliation::University,
+        Affiliation::UniversityRegion,
+        Affiliation::UniversityNews,
+        Affiliation::Web,
+    ];
+
+    /// The affiliation stored as `kind` in `source_affiliation`.
+    pub fn parse(kind: &str) -> Option<Affiliation> {
+        Affiliation::ALL.into_iter().find(|a| a.as_str() == kind)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Affiliation::Blog => "blog",
+            Affiliation::Bank => "bank",
+            Affiliation::Department => "department",
+            Affiliation::Journal => "journal",
+            Affiliation::Institution => "institution",
+            Affiliation::Senator => "senator",
+            Affiliation::DepartmentCountry => "department_country",
+            Affiliation::EmbassyCountry => "embassy_country",
+            Affiliation::EmbassyLocated => "embassy_located",
+            Affiliation::Facebook => "facebook",
+            Affiliation::NewsAgency => "news_agency",
+            Affiliation::Organization => "organization",
+            Affiliation::Representatives => "representatives",
+            Affiliation::RepresentativesRegion => "representatives_region",
+            Affiliation::SenatorRegion => "senator_region",
+            Affiliation::Twitter => "twitter",
+            Affiliation::University => "university",
+            Affiliation::UniversityRegion => "university_region",
+            Affiliation::UniversityNews => "university_news",
+            Affiliation::Web => "web",
+        }
+    }
+}
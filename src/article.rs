@@ -0,0 +1,119 @@
+use sha2::{Digest, Sha256};
+
+use crate::schema::Root;
+
+/// Natural key that identifies an article across records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArticleKey {
+    /// The headline alone, which merges different articles sharing a title.
+    Title,
+    /// A SHA-256 over headline, time, media id and original site.
+    Hash,
+    /// The `Media_Id` supplied with the record.
+    MediaId,
+}
+
+impl ArticleKey {
+    pub fn parse(s: &str) -> Option<ArticleKey> {
+        match s.trim().to_lowercase().as_str() {
+            "title" => Some(ArticleKey::Title),
+            "hash" => Some(ArticleKey::Hash),
+            "media_id" | "media-id" => Some(ArticleKey::MediaId),
+            _ => None,
+        }
+    }
+
+    /// Read `ARTICLE_KEY`, defaulting to the content hash.
+    pub fn from_env() -> ArticleKey {
+        std::env::var("ARTICLE_KEY")
+            .map(|s| ArticleKey::parse(&s).unwrap_or_else(|| panic!("Invalid ARTICLE_KEY: {}", s)))
+            .unwrap_or(ArticleKey::Hash)
+    }
+
+    pub fn derive(&self, root: &Root) -> Option<String> {
+        match self {
+            ArticleKey::Title => root.headline.clone(),
+            ArticleKey::MediaId => root.media_id.clone(),
+            ArticleKey::Hash => {
+                let mut hasher = Sha256::new();
+                for field in [&root.headline, &root.time, &root.media_id, &root.original_site] {
+                    // Distinguish a missing field from an empty one
+                    match field {
+                        Some(s) => {
+                            hasher.update([1]);
+                            hasher.update(s.as_bytes());
+                        }
+                        None => hasher.update([0]),
+                    }
+                    hasher.update([0x1f]);
+                }
+                Some(format!("{:x}", hasher.finalize()))
+            }
+        }
+    }
+}
+
+/// What to do with records that have no `Headline`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingHeadline {
+    /// Skip the record with a warning.
+    Skip,
+    /// Store the article with a NULL title, as long as its key can be derived.
+    Keep,
+}
+
+impl MissingHeadline {
+    /// Read `MISSING_HEADLINE`, defaulting to skip.
+    pub fn from_env() -> MissingHeadline {
+        match std::env::var("MISSING_HEADLINE").as_deref() {
+            Ok("keep") => MissingHeadline::Keep,
+            Ok("skip") | Err(_) => MissingHeadline::Skip,
+            Ok(s) => panic!("Invalid MISSING_HEADLINE: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(headline: Option<&str>, time: Option<&str>, media_id: Option<&str>) -> Root {
+        Root {
+            headline: headline.map(str::to_string),
+            time: time.map(str::to_string),
+            media_id: media_id.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hash_is_stable_and_covers_every_field() {
+        let hash = |r: &Root| ArticleKey::Hash.derive(r).unwrap();
+        let base = root(Some("Headline"), Some("2023-01-01"), Some("m1"));
+        assert_eq!(hash(&base), hash(&base.clone()));
+        assert_eq!(hash(&base).len(), 64);
+        for other in [
+            root(Some("Other"), Some("2023-01-01"), Some("m1")),
+            root(Some("Headline"), Some("2023-01-02"), Some("m1")),
+            root(Some("Headline"), Some("2023-01-01"), None),
+        ] {
+            assert_ne!(hash(&base), hash(&other));
+        }
+    }
+
+    #[test]
+    fn hash_tells_missing_from_empty_and_field_boundaries() {
+        let hash = |r: &Root| ArticleKey::Hash.derive(r).unwrap();
+        assert_ne!(hash(&root(None, None, None)), hash(&root(Some(""), None, None)));
+        assert_ne!(hash(&root(Some("ab"), Some("c"), None)), hash(&root(Some("a"), Some("bc"), None)));
+    }
+
+    #[test]
+    fn title_and_media_id_keys_need_their_field() {
+        let r = root(Some("Headline"), None, None);
+        assert_eq!(ArticleKey::Title.derive(&r).as_deref(), Some("Headline"));
+        assert_eq!(ArticleKey::MediaId.derive(&r), None);
+        assert_eq!(ArticleKey::parse(" Media-Id "), Some(ArticleKey::MediaId));
+        assert_eq!(ArticleKey::parse("url"), None);
+    }
+}
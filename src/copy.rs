@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use futures::TryStreamExt;
+use log::warn;
+use serde_json::Value;
+use sqlx::{Error, Pool, Postgres, Row};
+
+use crate::raw;
+use crate::upsert::Policy;
+
+/// Rows sent to the target per statement.
+const BATCH: usize = 1000;
+
+/// How one table is copied.
+struct Spec {
+    table: &'static str,
+    /// Columns inserted into the target, without `id`.
+    columns: &'static [&'static str],
+    /// Columns holding ids of earlier tables, remapped to the target's ids.
+    refs: &'static [(&'static str, &'static str)],
+    /// Resolves rows that already exist in the target.
+    conflict: String,
+    /// Matches a copied row `s` with its row `t` in the target, for tables whose ids are referenced.
+    key: Option<&'static str>,
+}
+
+/// The tables in dependency order. Entities are merged through their natural
+/// keys with the upsert policies of the migration, link tables are deduplicated.
+fn specs() -> Vec<Spec> {
+    let nothing = || "ON CONFLICT DO NOTHING".to_string();
+    vec![
+        Spec {
+            table: "country",
+            columns: &[
+                "name",
+                "iso_alpha2",
+                "iso_alpha3",
+                "region",
+                "geography",
+                "belt_and_road",
+                "bri_since",
+                "orob_region",
+                "geopolitics",
+                "updated_at",
+            ],
+            refs: &[],
+            conflict: Policy::from_env("country", Policy::MergeNonNull).on_conflict(
+                "country",
+                "name",
+                &["geography", "belt_and_road", "bri_since", "orob_region", "geopolitics"],
+            ),
+            key: Some("t.name = s.name"),
+        },
+        Spec {
+            table: "identity",
+            columns: &["name"],
+            refs: &[],
+            conflict: nothing(),
+            key: Some("t.name = s.name"),
+        },
+        Spec {
+            table: "source",
+            columns: &["name", "country_id", "updated_at"],
+            refs: &[("country_id", "country")],
+            conflict: Policy::from_env("source", Policy::FirstWins).on_conflict("source", "name", &["country_id"]),
+            key: Some("t.name = s.name"),
+        },
+        Spec {
+            table: "people",
+            columns: &["name", "country_id", "title", "origin", "updated_at"],
+            refs: &[("country_id", "country")],
+            conflict: Policy::from_env("people", Policy::FirstWins).on_conflict(
+                "people",
+                "name, (COALESCE(country_id, 0))",
+                &["origin", "title"],
+            ),
+            key: Some("t.name = s.name AND COALESCE(t.country_id, 0) = COALESCE(s.country_id, 0)"),
+        },
+        Spec {
+            table: "article",
+            columns: &["key", "title", "time", "media_id", "original_site", "abstract", "body", "updated_at"],
+            refs: &[],
+            conflict: Policy::from_env("article", Policy::FirstWins).on_conflict(
+                "article",
+                "key",
+                &["title", "time", "media_id", "original_site", "abstract", "body"],
+            ),
+            key: Some("t.key = s.key"),
+        },
+        Spec {
+            table: "source_alias",
+            columns: &["source_id", "alias"],
+            refs: &[("source_id", "source")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "source_affiliation",
+            columns: &["source_id", "kind", "value"],
+            refs: &[("source_id", "source")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "people_alias",
+            columns: &["people_id", "alias"],
+            refs: &[("people_id", "people")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "people_identity",
+            columns: &["people_id", "identity_id", "value"],
+            refs: &[("people_id", "people"), ("identity_id", "identity")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "people_account",
+            columns: &["people_id", "platform", "account", "url", "screenshot"],
+            refs: &[("people_id", "people")],
+            conflict: "ON CONFLICT (people_id, platform, account) \
+                DO UPDATE SET url = COALESCE(people_account.url, EXCLUDED.url), \
+                screenshot = COALESCE(people_account.screenshot, EXCLUDED.screenshot)"
+                .to_string(),
+            key: None,
+        },
+        Spec {
+            table: "legislator_term",
+            columns: &["people_id", "chamber", "state", "district", "party", "period", "start_year", "end_year"],
+            refs: &[("people_id", "people")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "source_article",
+            columns: &["source_id", "article_id"],
+            refs: &[("source_id", "source"), ("article_id", "article")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "article_pipeline_state",
+            columns: &["article_id", "stage", "status", "raw"],
+            refs: &[("article_id", "article")],
+            conflict: "ON CONFLICT (article_id, stage) DO UPDATE SET status = EXCLUDED.status, raw = EXCLUDED.raw"
+                .to_string(),
+            key: None,
+        },
+        Spec {
+            table: "opinion",
+            columns: &["author_id", "text", "article_id", "score", "span_start", "span_end"],
+            refs: &[("author_id", "people"), ("article_id", "article")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "country_conflict",
+            columns: &["country_id", "attribute", "stored", "incoming"],
+            refs: &[("country_id", "country")],
+            conflict: nothing(),
+            key: None,
+        },
+        Spec {
+            table: "country_unmatched",
+            columns: &["name", "occurrences", "first_seen"],
+            refs: &[],
+            conflict: "ON CONFLICT (name) DO UPDATE SET \
+                occurrences = country_unmatched.occurrences + EXCLUDED.occurrences, \
+                first_seen = LEAST(country_unmatched.first_seen, EXCLUDED.first_seen)"
+                .to_string(),
+            key: None,
+        },
+        Spec {
+            table: "raw_record",
+            columns: &["hash", "record", "run_id", "file", "position"],
+            refs: &[("run_id", "import_run")],
+            conflict: nothing(),
+            key: None,
+        },
+    ]
+}
+
+/// Copy the migrated tables of `source` into `target`, whose tables must exist.
+///
+/// Rows get the target's ids: countries, sources and identities are matched by
+/// name, people by name and country, and articles by their stored key, so both
+/// databases should use the same `ARTICLE_KEY`. Rows that already exist in the
+/// target are merged according to the upsert policies instead of duplicated.
+/// Archived raw records are attributed to a new import run of the target, and
+/// the entity audit is not copied since it refers to the source's ids.
+/// The source is only read.
+pub async fn copy(source: &Pool<Postgres>, target: &Pool<Postgres>) -> Result<(), Error> {
+    let run_id = raw::start_run(target).await?;
+    let mut ids: HashMap<&str, HashMap<i64, i64>> = HashMap::new();
+    ids.insert(
+        "import_run",
+        sqlx::query("SELECT id FROM import_run")
+            .fetch_all(source)
+            .await?
+            .iter()
+            .map(|row| (row.get::<i32, _>("id") as i64, run_id as i64))
+            .collect(),
+    );
+
+    for spec in specs() {
+        let select = format!("SELECT (to_jsonb(s) - 'search')::TEXT AS row FROM {} s", spec.table);
+        let mut rows = sqlx::query(&select).fetch(source);
+        let (mut batch, mut copied, mut orphans) = (vec![], 0, 0);
+        while let Some(row) = rows.try_next().await? {
+            let mut value: Value = serde_json::from_str(row.get("row")).unwrap();
+            if remap(&mut value, spec.refs, &ids) {
+                batch.push(value);
+            } else {
+                orphans += 1;
+            }
+            if batch.len() == BATCH {
+                copied += insert(target, &spec, &batch, &mut ids).await?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            copied += insert(target, &spec, &batch, &mut ids).await?;
+        }
+        warn!("Copied {} rows of {}", copied, spec.table);
+        if orphans > 0 {
+            warn!("Skipped {} rows of {} referencing rows that were not copied", orphans, spec.table);
+        }
+    }
+    raw::finish_run(target, run_id).await?;
+    Ok(())
+}
+
+/// Replace the ids in the `refs` columns of `row` with the target's, returning
+/// false when one of them is unknown.
+fn remap(row: &mut Value, refs: &[(&str, &str)], ids: &HashMap<&str, HashMap<i64, i64>>) -> bool {
+    for (column, table) in refs {
+        let Some(id) = row[column].as_i64() else {
+            continue;
+        };
+        match ids.get(table).and_then(|ids| ids.get(&id)) {
+            Some(&new) => row[column] = new.into(),
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Insert `batch` into the target, returning how many rows were inserted or
+/// updated, and record the target ids of keyed tables.
+async fn insert(
+    target: &Pool<Postgres>,
+    spec: &Spec,
+    batch: &[Value],
+    ids: &mut HashMap<&str, HashMap<i64, i64>>,
+) -> Result<u64, Error> {
+    let columns = spec.columns.join(", ");
+    let sql = format!(
+        "INSERT INTO {t} ({c}) SELECT {c} FROM jsonb_populate_recordset(NULL::{t}, $1::JSONB) {}",
+        spec.conflict,
+        t = spec.table,
+        c = columns,
+    );
+    let rows = Value::Array(batch.to_vec()).to_string();
+    let copied = match sqlx::query(&sql).bind(&rows).execute(target).await {
+        Ok(result) => result.rows_affected(),
+        // Text too long for a unique index, retry row by row to skip only those
+        Err(e) if e.as_database_error().and_then(|e| e.code()).as_deref() == Some("54000") => {
+            let mut copied = 0;
+            for row in batch {
+                match sqlx::query(&sql).bind(Value::Array(vec![row.clone()]).to_string()).execute(target).await {
+                    Ok(result) => copied += result.rows_affected(),
+                    Err(e) if e.as_database_error().and_then(|e| e.code()).as_deref() == Some("54000") => {
+                        warn!("Skipped a row of {} too long to index", spec.table)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            copied
+        }
+        Err(e) => return Err(e),
+    };
+
+    if let Some(key) = spec.key {
+        let mapped = ids.entry(spec.table).or_default();
+        for row in sqlx::query(&format!(
+            "SELECT s.id AS old, t.id AS new FROM jsonb_populate_recordset(NULL::{t}, $1::JSONB) s \
+            JOIN {t} t ON {}",
+            key,
+            t = spec.table,
+        ))
+        .bind(&rows)
+        .fetch_all(target)
+        .await?
+        {
+            mapped.insert(row.get::<i32, _>("old") as i64, row.get::<i32, _>("new") as i64);
+        }
+    }
+    Ok(copied)
+}
@@ -0,0 +1,481 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use log::warn;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::offsets::Convention;
+use crate::table::{Table, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A JSON array of `Doc.to_json()` documents, for `Doc.from_json` and `DocBin`
+    Spacy,
+    /// Tab separated token, BIO tag and speaker, one token per line
+    Conll,
+    /// One document per line with its text and spans, as used by Prodigy
+    Jsonl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    Train,
+    Dev,
+    Test,
+}
+
+impl Split {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Split::Train => "train",
+            Split::Dev => "dev",
+            Split::Test => "test",
+        }
+    }
+}
+
+/// An opinion located in an article body, in character offsets.
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    /// The score bucket.
+    pub label: &'static str,
+    pub speaker: String,
+    pub score: f64,
+}
+
+/// An article body with the opinions quoted in it.
+pub struct Document {
+    pub key: String,
+    /// Name of the first source publishing the article, which splits are stratified by.
+    pub source: String,
+    pub text: String,
+    pub spans: Vec<Span>,
+}
+
+/// Label a score as `NEGATIVE`, `NEUTRAL` within `neutral` of zero, or `POSITIVE`.
+pub fn bucket(score: f64, neutral: f64) -> &'static str {
+    if score < -neutral {
+        "NEGATIVE"
+    } else if score > neutral {
+        "POSITIVE"
+    } else {
+        "NEUTRAL"
+    }
+}
+
+/// Build a document for each article with a body and at least one opinion
+/// whose span lies within it and covers its text, in char offsets. Other
+/// spans are dropped with a warning.
+pub fn documents(tables: &[Table], neutral: f64) -> Vec<Document> {
+    let table = |name: &str| {
+        tables
+            .iter()
+            .find(|t| t.name == name)
+            .unwrap_or_else(|| panic!("Missing table: {}", name))
+    };
+    let int = |v: &Value| match v {
+        Value::Int(i) => Some(*i),
+        _ => None,
+    };
+    let text = |v: &Value| match v {
+        Value::Text(s) => Some(s.clone()),
+        _ => None,
+    };
+
+    let people = table("people");
+    let speakers: HashMap<i32, String> = people
+        .rows
+        .iter()
+        .filter_map(|row| Some((int(&row[0])?, text(&row[people.column("name")])?)))
+        .collect();
+    let source = table("source");
+    let sources: HashMap<i32, String> = source
+        .rows
+        .iter()
+        .filter_map(|row| Some((int(&row[0])?, text(&row[source.column("name")])?)))
+        .collect();
+    let links = table("source_article");
+    let mut first_source: HashMap<i32, i32> = HashMap::new();
+    for row in &links.rows {
+        let (Some(source_id), Some(article_id)) = (int(&row[links.column("source_id")]), int(&row[links.column("article_id")])) else {
+            continue;
+        };
+        let first = first_source.entry(article_id).or_insert(source_id);
+        *first = (*first).min(source_id);
+    }
+
+    let opinion = table("opinion");
+    let mut spans: HashMap<i32, Vec<Span>> = HashMap::new();
+    let article = table("article");
+    let bodies: HashMap<i32, String> = article
+        .rows
+        .iter()
+        .filter_map(|row| Some((int(&row[0])?, text(&row[article.column("body")])?)))
+        .collect();
+    let (mut dropped, mut misaligned) = (0, 0);
+    for row in &opinion.rows {
+        let Some(article_id) = int(&row[opinion.column("article_id")]) else {
+            continue;
+        };
+        let (Value::BigInt(start), Value::BigInt(end)) = (&row[opinion.column("span_start")], &row[opinion.column("span_end")]) else {
+            dropped += 1;
+            continue;
+        };
+        let (Ok(start), Ok(end)) = (usize::try_from(*start), usize::try_from(*end)) else {
+            dropped += 1;
+            continue;
+        };
+        let Some(body) = bodies.get(&article_id).filter(|body| start < end && end <= body.chars().count()) else {
+            dropped += 1;
+            continue;
+        };
+        // The span must cover the opinion's own text, or the labels would mark other words
+        if !text(&row[opinion.column("text")]).is_some_and(|text| Convention::Char.matches(body, start, end, &text)) {
+            misaligned += 1;
+            continue;
+        }
+        let score = match row[opinion.column("score")] {
+            Value::Float(score) => score,
+            _ => 0.0,
+        };
+        spans.entry(article_id).or_default().push(Span {
+            start,
+            end,
+            label: bucket(score, neutral),
+            speaker: int(&row[opinion.column("author_id")])
+                .and_then(|id| speakers.get(&id).cloned())
+                .unwrap_or_default(),
+            score,
+        });
+    }
+    if dropped > 0 {
+        warn!("Dropped {} opinions without a span inside the article body", dropped);
+    }
+    if misaligned > 0 {
+        warn!("Dropped {} opinions whose span does not cover their text", misaligned);
+    }
+
+    article
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let id = int(&row[0])?;
+            let mut spans = spans.remove(&id)?;
+            spans.sort_by_key(|s| (s.start, s.end));
+            Some(Document {
+                key: text(&row[article.column("key")])?,
+                source: first_source
+                    .get(&id)
+                    .and_then(|s| sources.get(s).cloned())
+                    .unwrap_or_default(),
+                text: text(&row[article.column("body")])?,
+                spans,
+            })
+        })
+        .collect()
+}
+
+/// Assign each document to a split, stratified by source: within the
+/// documents of each source, ordered by a hash of the article key and `seed`,
+/// the first `test` fraction goes to test, the next `dev` fraction to dev and
+/// the rest to train. A source with few documents may have none in dev or test.
+pub fn split(documents: &[Document], dev: f64, test: f64, seed: u64) -> Vec<Split> {
+    let mut groups: BTreeMap<&str, Vec<(Vec<u8>, usize)>> = BTreeMap::new();
+    for (i, document) in documents.iter().enumerate() {
+        let hash = Sha256::digest(format!("{}:{}", seed, document.key).as_bytes()).to_vec();
+        groups.entry(&document.source).or_default().push((hash, i));
+    }
+    let mut splits = vec![Split::Train; documents.len()];
+    for mut group in groups.into_values() {
+        group.sort();
+        let n = group.len() as f64;
+        let (tests, devs) = ((n * test).round() as usize, (n * dev).round() as usize);
+        for (rank, (_, i)) in group.into_iter().enumerate() {
+            splits[i] = if rank < tests {
+                Split::Test
+            } else if rank < tests + devs {
+                Split::Dev
+            } else {
+                Split::Train
+            };
+        }
+    }
+    splits
+}
+
+/// Whether a character is written without spaces between words.
+fn is_ideograph(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+/// Character ranges of the tokens of `text`: runs of letters and digits, and
+/// every ideograph or other symbol on its own. Tokens also break at `breaks`
+/// so that spans always cover whole tokens.
+fn tokens(text: &str, breaks: &BTreeSet<usize>) -> Vec<(usize, usize)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in text.chars().enumerate() {
+        let word = c.is_alphanumeric() && !is_ideograph(c);
+        if let Some(s) = start {
+            if !word || breaks.contains(&i) {
+                tokens.push((s, i));
+                start = None;
+            }
+        }
+        if word {
+            start.get_or_insert(i);
+        } else if !c.is_whitespace() {
+            tokens.push((i, i + 1));
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s, text.chars().count()));
+    }
+    tokens
+}
+
+/// Byte offset of every character of `text`, and of its end.
+fn byte_offsets(text: &str) -> Vec<usize> {
+    text.char_indices().map(|(i, _)| i).chain([text.len()]).collect()
+}
+
+fn spacy(document: &Document) -> serde_json::Value {
+    let breaks = document.spans.iter().flat_map(|s| [s.start, s.end]).collect();
+    let tokens: Vec<_> = tokens(&document.text, &breaks)
+        .into_iter()
+        .enumerate()
+        .map(|(id, (start, end))| json!({ "id": id, "start": start, "end": end }))
+        .collect();
+    let spans: Vec<_> = document
+        .spans
+        .iter()
+        .map(|s| json!({ "start": s.start, "end": s.end, "label": s.label, "kb_id": s.speaker }))
+        .collect();
+    json!({ "text": document.text, "tokens": tokens, "spans": { "sc": spans } })
+}
+
+fn jsonl(document: &Document, split: Split) -> serde_json::Value {
+    let bytes = byte_offsets(&document.text);
+    let spans: Vec<_> = document
+        .spans
+        .iter()
+        .map(|s| {
+            json!({
+                "start": s.start,
+                "end": s.end,
+                "label": s.label,
+                "speaker": s.speaker,
+                "score": s.score,
+                "text": &document.text[bytes[s.start]..bytes[s.end]],
+            })
+        })
+        .collect();
+    json!({
+        "text": document.text,
+        "spans": spans,
+        "meta": { "key": document.key, "source": document.source, "split": split.as_str() },
+    })
+}
+
+/// Write the tokens of `document` with BIO tags and speakers, a blank line
+/// after each sentence. Where spans overlap the earlier one is kept, and the
+/// number of spans left out is returned.
+fn conll(document: &Document, w: &mut impl Write) -> std::io::Result<usize> {
+    let breaks = document.spans.iter().flat_map(|s| [s.start, s.end]).collect();
+    let tokens = tokens(&document.text, &breaks);
+    let mut tags: Vec<Option<(bool, &Span)>> = vec![None; tokens.len()];
+    let mut skipped = 0;
+    for span in &document.spans {
+        let covered: Vec<usize> = (0..tokens.len())
+            .filter(|&i| tokens[i].0 >= span.start && tokens[i].1 <= span.end)
+            .collect();
+        if covered.is_empty() || covered.iter().any(|&i| tags[i].is_some()) {
+            skipped += 1;
+            continue;
+        }
+        for (n, &i) in covered.iter().enumerate() {
+            tags[i] = Some((n == 0, span));
+        }
+    }
+
+    let bytes = byte_offsets(&document.text);
+    writeln!(w, "-DOCSTART-\t-X-\t{}", document.key)?;
+    writeln!(w)?;
+    let mut open = false;
+    for ((start, end), tag) in tokens.into_iter().zip(tags) {
+        let token = &document.text[bytes[start]..bytes[end]];
+        match tag {
+            Some((first, span)) => {
+                let speaker = span.speaker.split_whitespace().collect::<Vec<_>>().join("_");
+                let speaker = if speaker.is_empty() { "_".to_string() } else { speaker };
+                writeln!(w, "{}\t{}-{}\t{}", token, if first { "B" } else { "I" }, span.label, speaker)?
+            }
+            None => writeln!(w, "{}\tO\t_", token)?,
+        }
+        open = !matches!(token, "." | "!" | "?" | "。" | "！" | "？");
+        if !open {
+            writeln!(w)?;
+        }
+    }
+    if open {
+        writeln!(w)?;
+    }
+    Ok(skipped)
+}
+
+/// Write `<split>.spacy.json`, `<split>.conll` and `<split>.jsonl` under `dir` for each of `formats`.
+pub fn write(documents: &[Document], splits: &[Split], dir: &Path, formats: &[Format]) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    for split in [Split::Train, Split::Dev, Split::Test] {
+        let documents: Vec<&Document> = documents
+            .iter()
+            .zip(splits)
+            .filter(|(_, s)| **s == split)
+            .map(|(d, _)| d)
+            .collect();
+        warn!(
+            "Writing {} documents with {} spans to {}",
+            documents.len(),
+            documents.iter().map(|d| d.spans.len()).sum::<usize>(),
+            split.as_str(),
+        );
+        for format in formats {
+            match format {
+                Format::Spacy => {
+                    let docs: Vec<_> = documents.iter().map(|d| spacy(d)).collect();
+                    let mut w = BufWriter::new(File::create(dir.join(format!("{}.spacy.json", split.as_str())))?);
+                    serde_json::to_writer(&mut w, &docs)?;
+                    w.flush()?;
+                }
+                Format::Conll => {
+                    let mut w = BufWriter::new(File::create(dir.join(format!("{}.conll", split.as_str())))?);
+                    let mut skipped = 0;
+                    for document in &documents {
+                        skipped += conll(document, &mut w)?;
+                    }
+                    w.flush()?;
+                    if skipped > 0 {
+                        warn!("Left {} overlapping spans out of {}.conll", skipped, split.as_str());
+                    }
+                }
+                Format::Jsonl => {
+                    let mut w = BufWriter::new(File::create(dir.join(format!("{}.jsonl", split.as_str())))?);
+                    for document in &documents {
+                        serde_json::to_writer(&mut w, &jsonl(document, split))?;
+                        writeln!(w)?;
+                    }
+                    w.flush()?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(key: &str, source: &str) -> Document {
+        Document { key: key.to_string(), source: source.to_string(), text: String::new(), spans: vec![] }
+    }
+
+    fn span(start: usize, end: usize) -> Span {
+        Span { start, end, label: "NEUTRAL", speaker: String::new(), score: 0.0 }
+    }
+
+    fn corpus() -> Vec<Document> {
+        (0..100)
+            .map(|i| document(&format!("a{}", i), "A"))
+            .chain((0..40).map(|i| document(&format!("b{}", i), "B")))
+            .collect()
+    }
+
+    #[test]
+    fn split_is_deterministic_for_a_seed() {
+        let documents = corpus();
+        assert_eq!(split(&documents, 0.1, 0.2, 7), split(&documents, 0.1, 0.2, 7));
+        assert_ne!(split(&documents, 0.1, 0.2, 7), split(&documents, 0.1, 0.2, 8));
+    }
+
+    #[test]
+    fn split_is_stratified_by_source() {
+        let documents = corpus();
+        let splits = split(&documents, 0.1, 0.2, 0);
+        let count = |source: &str, wanted: Split| {
+            documents.iter().zip(&splits).filter(|(d, s)| d.source == source && **s == wanted).count()
+        };
+        assert_eq!((count("A", Split::Train), count("A", Split::Dev), count("A", Split::Test)), (70, 10, 20));
+        assert_eq!((count("B", Split::Train), count("B", Split::Dev), count("B", Split::Test)), (28, 4, 8));
+    }
+
+    /// No token crosses a span boundary, and every span starts and ends on one.
+    fn assert_aligned(text: &str, spans: &[Span]) {
+        let breaks = spans.iter().flat_map(|s| [s.start, s.end]).collect();
+        let tokens = tokens(text, &breaks);
+        for span in spans {
+            for &(start, end) in &tokens {
+                let inside = start >= span.start && end <= span.end;
+                let outside = end <= span.start || start >= span.end;
+                assert!(inside || outside, "token {}..{} crosses span {}..{}", start, end, span.start, span.end);
+            }
+            assert!(tokens.iter().any(|t| t.0 == span.start), "no token starts at {}", span.start);
+            assert!(tokens.iter().any(|t| t.1 == span.end), "no token ends at {}", span.end);
+        }
+    }
+
+    #[test]
+    fn tokens_break_at_spans() {
+        // Spans starting and ending inside words
+        assert_aligned("The senator said cooperation matters.", &[span(6, 16), span(19, 28)]);
+    }
+
+    #[test]
+    fn tokens_break_at_spans_in_cjk_text() {
+        let text = "参议员说：中美合作很重要。Trade在2023年增长了。";
+        assert_aligned(text, &[span(5, 12), span(13, 18), span(14, 16)]);
+        let breaks = BTreeSet::new();
+        let tokens = tokens(text, &breaks);
+        assert_eq!(tokens[0], (0, 1));
+        assert!(tokens.contains(&(13, 18)));
+    }
+
+    #[test]
+    fn documents_drop_spans_that_miss_their_text() {
+        let mut article = Table::new("article");
+        article.rows.push(vec![
+            Value::Int(1),
+            Value::Text("k1".to_string()),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Text("😀 中国很重要 ok".to_string()),
+            Value::Null,
+        ]);
+        let mut opinion = Table::new("opinion");
+        for (id, text, start, end) in [(1, "中国很重要", 2, 7), (2, "中国很重要", 3, 8), (3, "ok", 8, 20)] {
+            opinion.rows.push(vec![
+                Value::Int(id),
+                Value::Int(1),
+                Value::Text(text.to_string()),
+                Value::Int(1),
+                Value::Float(0.5),
+                Value::BigInt(start),
+                Value::BigInt(end),
+            ]);
+        }
+        let tables = [Table::new("people"), Table::new("source"), Table::new("source_article"), article, opinion];
+        let documents = documents(&tables, 0.1);
+        assert_eq!(documents.len(), 1);
+        let spans: Vec<_> = documents[0].spans.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(spans, vec![(2, 7)]);
+    }
+}
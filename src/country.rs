@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use sqlx::{Error, Pool, Postgres, Row};
+
+use crate::normalize::Normalizer;
+
+/// ISO 3166 countries with regions and common aliases, one per line as
+/// `alpha2, alpha3, name, region, aliases` separated by tabs, aliases by `|`.
+const BUNDLED: &str = include_str!("countries.tsv");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Country {
+    pub alpha2: &'static str,
+    pub alpha3: &'static str,
+    pub name: &'static str,
+    pub region: &'static str,
+}
+
+/// Resolves the country names found in records to the bundled reference data.
+pub struct Reference {
+    countries: Vec<Country>,
+    codes: HashMap<&'static str, usize>,
+    names: HashMap<String, usize>,
+}
+
+/// How names are compared with the reference data. Honorifics are kept, as
+/// stripping them would read `DR Congo` as `Congo`.
+fn normalizer() -> Normalizer {
+    Normalizer {
+        honorifics: false,
+        ..Normalizer::all()
+    }
+}
+
+impl Reference {
+    /// The bundled reference data. Panics if two countries share a name once
+    /// normalised, as one would silently shadow the other.
+    pub fn bundled() -> Reference {
+        let normalizer = normalizer();
+        let mut reference = Reference {
+            countries: vec![],
+            codes: HashMap::new(),
+            names: HashMap::new(),
+        };
+        for line in BUNDLED.lines().filter(|l| !l.trim().is_empty()) {
+            let fields: Vec<&'static str> = line.split('\t').collect();
+            let [alpha2, alpha3, name, region, aliases] = fields[..] else {
+                panic!("Invalid country reference line: {}", line);
+            };
+            let i = reference.countries.len();
+            reference.codes.insert(alpha2, i);
+            reference.codes.insert(alpha3, i);
+            for alias in std::iter::once(name).chain(aliases.split('|').filter(|a| !a.is_empty())) {
+                if let Some(other) = reference.names.insert(normalizer.apply(alias), i).filter(|&j| j != i) {
+                    panic!("Country alias {} of {} is also a name of {}", alias, name, reference.countries[other].name);
+                }
+            }
+            reference.countries.push(Country { alpha2, alpha3, name, region });
+        }
+        reference
+    }
+
+    pub fn countries(&self) -> &[Country] {
+        &self.countries
+    }
+
+    /// Look up a country by ISO code, name or alias.
+    ///
+    /// Codes only match when written in upper case, so that e.g. `N/A` or
+    /// `no` are not taken for Namibia or Norway.
+    pub fn resolve(&self, raw: &str) -> Option<&Country> {
+        let raw = raw.trim();
+        self.codes
+            .get(raw)
+            .or_else(|| self.names.get(&normalizer().apply(raw)))
+            .map(|&i| &self.countries[i])
+    }
+}
+
+/// Count a country name that is not in the reference data, returning
+/// whether this is the first time it was seen.
+pub async fn report_unmatched(pool: &Pool<Postgres>, raw: &str) -> Result<bool, Error> {
+    Ok(sqlx::query(
+        "INSERT INTO country_unmatched (name) VALUES ($1) \
+        ON CONFLICT (name) DO UPDATE SET occurrences = country_unmatched.occurrences + 1 \
+        RETURNING occurrences",
+    )
+    .bind(raw)
+    .fetch_one(pool)
+    .await?
+    .get::<i32, _>("occurrences")
+        == 1)
+}
+
+/// Belt and Road membership parsed from an `Orob` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Orob {
+    pub member: bool,
+    /// Start of membership, when the value carries a date such as `Yes (2017-05)`.
+    pub since: Option<NaiveDate>,
+}
+
+impl Orob {
+    /// Format as an `Orob` value that [`parse_orob`] reads back, such as `Yes (2017-05-01)`.
+    pub fn to_raw(self) -> String {
+        match (self.member, self.since) {
+            (true, Some(since)) => format!("Yes ({})", since),
+            (true, None) => "Yes".to_string(),
+            (false, _) => "No".to_string(),
+        }
+    }
+}
+
+/// Parse `Yes`, `No`, `1`, `0`, `是`, `否` and the like, with an optional year or date.
+pub fn parse_orob(raw: &str) -> Option<Orob> {
+    let lower = raw.trim().to_lowercase();
+    let word = lower
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .next()
+        .unwrap_or_default();
+    let member = match word {
+        "yes" | "y" | "true" | "t" | "1" | "member" | "是" => true,
+        "no" | "n" | "false" | "f" | "0" | "none" | "non-member" | "否" => false,
+        _ if lower.starts_with('是') => true,
+        _ if lower.starts_with('否') => false,
+        _ => return None,
+    };
+    let since = if member { parse_since(&lower) } else { None };
+    Some(Orob { member, since })
+}
+
+/// Find the first `YYYY`, `YYYY-MM` or `YYYY-MM-DD` in `s`.
+fn parse_since(s: &str) -> Option<NaiveDate> {
+    let numbers: Vec<&str> = s
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|n| !n.is_empty())
+        .collect();
+    let start = numbers.iter().position(|n| n.len() == 4)?;
+    let mut parts = numbers[start..].iter().map(|n| n.parse::<u32>().ok());
+    let year = parts.next()??;
+    let month = parts.next().flatten().filter(|m| (1..=12).contains(m));
+    let day = month.and(parts.next().flatten());
+    NaiveDate::from_ymd_opt(year as i32, month.unwrap_or(1), day.unwrap_or(1))
+        .or_else(|| NaiveDate::from_ymd_opt(year as i32, month.unwrap_or(1), 1))
+}
+
+/// Country attributes carried by a `Source` or `People` record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attributes<'a> {
+    pub geography: Option<&'a str>,
+    pub orob: Option<Orob>,
+    pub orob_region: Option<&'a str>,
+    pub geopolitics: Option<&'a str>,
+}
+
+/// Compare `attributes` with what is stored for the country and record the
+/// values that disagree, returning those not seen before as
+/// `(attribute, stored, incoming)`.
+pub async fn record_conflicts(
+    pool: &Pool<Postgres>,
+    name: &str,
+    attributes: &Attributes<'_>,
+) -> Result<Vec<(&'static str, String, String)>, Error> {
+    let Some(row) = sqlx::query(
+        "SELECT id, geography, belt_and_road, orob_region, geopolitics FROM country WHERE name = $1",
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(vec![]);
+    };
+    let stored = [
+        ("geography", row.get::<Option<String>, _>("geography")),
+        ("belt_and_road", row.get::<Option<bool>, _>("belt_and_road").map(|b| b.to_string())),
+        ("orob_region", row.get::<Option<String>, _>("orob_region")),
+        ("geopolitics", row.get::<Option<String>, _>("geopolitics")),
+    ];
+    let incoming = [
+        attributes.geography.map(str::to_string),
+        attributes.orob.map(|o| o.member.to_string()),
+        attributes.orob_region.map(str::to_string),
+        attributes.geopolitics.map(str::to_string),
+    ];
+    let mut conflicts = vec![];
+    for ((attribute, stored), incoming) in stored.into_iter().zip(incoming) {
+        let (Some(stored), Some(incoming)) = (stored, incoming) else {
+            continue;
+        };
+        if stored.trim() == incoming.trim() {
+            continue;
+        }
+        let inserted = sqlx::query(
+            "INSERT INTO country_conflict (country_id, attribute, stored, incoming) \
+            VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+        )
+        .bind(row.get::<i32, _>("id"))
+        .bind(attribute)
+        .bind(&stored)
+        .bind(&incoming)
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            conflicts.push((attribute, stored, incoming));
+        }
+    }
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_names_are_unique() {
+        // Panics on a collision
+        let reference = Reference::bundled();
+        assert_eq!(reference.countries().len(), 250);
+    }
+
+    #[test]
+    fn resolves_codes_names_and_aliases() {
+        let reference = Reference::bundled();
+        for (raw, alpha2) in [
+            ("DR Congo", "CD"),
+            ("Congo-Brazzaville", "CG"),
+            ("congo", "CG"),
+            ("COD", "CD"),
+            ("  republic of albania ", "AL"),
+        ] {
+            assert_eq!(reference.resolve(raw).map(|c| c.alpha2), Some(alpha2), "{}", raw);
+        }
+        // Codes only match in upper case
+        assert!(reference.resolve("no").is_none());
+    }
+
+    fn orob(member: bool, since: Option<(i32, u32, u32)>) -> Option<Orob> {
+        let since = since.map(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d).unwrap());
+        Some(Orob { member, since })
+    }
+
+    #[test]
+    fn parses_orob_membership_and_start() {
+        for (raw, expected) in [
+            ("Yes", orob(true, None)),
+            (" 1 ", orob(true, None)),
+            ("是", orob(true, None)),
+            ("是（2013年）", orob(true, Some((2013, 1, 1)))),
+            ("是2015", orob(true, Some((2015, 1, 1)))),
+            ("Yes (2017-05)", orob(true, Some((2017, 5, 1)))),
+            ("member since 2019-03-23", orob(true, Some((2019, 3, 23)))),
+            ("Yes 2018-02-30", orob(true, Some((2018, 2, 1)))),
+            ("No", orob(false, None)),
+            ("non-member", orob(false, None)),
+            ("否", orob(false, None)),
+            // A year is not read for non-members
+            ("No (2014)", orob(false, None)),
+            ("maybe", None),
+            ("", None),
+        ] {
+            assert_eq!(parse_orob(raw), expected, "{}", raw);
+            if let Some(orob) = expected {
+                assert_eq!(parse_orob(&orob.to_raw()), expected, "{}", orob.to_raw());
+            }
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use log::warn;
+use sqlx::postgres::PgRow;
+use sqlx::{Pool, Postgres, Row};
+
+use crate::account::Platform;
+use crate::affiliation::Affiliation;
+use crate::country::Orob;
+use crate::identity::Identity;
+use crate::pipeline::Stage;
+use crate::schema::{Opinion, People, Root, Source};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Documents reconstructed per round trip.
+const PAGE: i64 = 1000;
+
+/// Which documents to export. Empty lists match everything.
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// First day of the article time, inclusive, in UTC.
+    pub since: Option<NaiveDate>,
+    /// Last day of the article time, inclusive, in UTC.
+    pub until: Option<NaiveDate>,
+    /// Country names or ISO codes of the person or of any source, case-insensitive.
+    pub countries: Vec<String>,
+    /// Source names.
+    pub sources: Vec<String>,
+    /// Person names or aliases.
+    pub people: Vec<String>,
+}
+
+/// Matches a country alias `c` against the lowercased `$7`.
+const COUNTRY_MATCH: &str =
+    "(lower({c}.name) = ANY($7) OR lower({c}.iso_alpha2) = ANY($7) OR lower({c}.iso_alpha3) = ANY($7))";
+
+/// Write the `Root` documents matching `filter` to `output` as a JSON array,
+/// or one document per line with `ndjson`, returning how many were written.
+///
+/// There is one document per article and opinion author, holding only that
+/// author's opinions, and one without `People` for articles without opinions.
+/// Fields that are not stored, such as `Keywords` or `Topic`, are left out, and
+/// the merged `origin` of a person is not split back into the `From_*` fields.
+pub async fn export(pool: &Pool<Postgres>, filter: &Filter, output: &Path, ndjson: bool) -> Result<u64> {
+    let countries: Vec<String> = filter.countries.iter().map(|c| c.trim().to_lowercase()).collect();
+    let sql = format!(
+        "SELECT a.id AS article_id, o.author_id FROM article a \
+        LEFT JOIN (SELECT DISTINCT article_id, author_id FROM opinion) o ON o.article_id = a.id \
+        LEFT JOIN people p ON p.id = o.author_id \
+        LEFT JOIN country pc ON pc.id = p.country_id \
+        WHERE (a.id, COALESCE(o.author_id, 0)) > ($1, $2) \
+        AND ($3::date IS NULL OR a.time >= $3::date::timestamp AT TIME ZONE 'UTC') \
+        AND ($4::date IS NULL OR a.time < ($4::date + 1)::timestamp AT TIME ZONE 'UTC') \
+        AND (cardinality($5::text[]) = 0 OR EXISTS (SELECT 1 FROM source_article sa \
+            JOIN source s ON s.id = sa.source_id WHERE sa.article_id = a.id AND s.name = ANY($5))) \
+        AND (cardinality($6::text[]) = 0 OR p.name = ANY($6) OR EXISTS (SELECT 1 FROM people_alias pa \
+            WHERE pa.people_id = p.id AND pa.alias = ANY($6))) \
+        AND (cardinality($7::text[]) = 0 OR {} OR EXISTS (SELECT 1 FROM source_article sa \
+            JOIN source s ON s.id = sa.source_id JOIN country sc ON sc.id = s.country_id \
+            WHERE sa.article_id = a.id AND {})) \
+        ORDER BY a.id, COALESCE(o.author_id, 0) LIMIT $8",
+        COUNTRY_MATCH.replace("{c}", "pc"),
+        COUNTRY_MATCH.replace("{c}", "sc"),
+    );
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    if !ndjson {
+        writer.write_all(b"[")?;
+    }
+    let mut written = 0;
+    let mut after = (0, 0);
+    loop {
+        let pairs: Vec<(i32, Option<i32>)> = sqlx::query(&sql)
+            .bind(after.0)
+            .bind(after.1)
+            .bind(filter.since)
+            .bind(filter.until)
+            .bind(&filter.sources)
+            .bind(&filter.people)
+            .bind(&countries)
+            .bind(PAGE)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| (row.get("article_id"), row.get("author_id")))
+            .collect();
+        let Some(&(article_id, author_id)) = pairs.last() else {
+            break;
+        };
+        after = (article_id, author_id.unwrap_or(0));
+        for root in documents(pool, &pairs).await? {
+            if ndjson {
+                serde_json::to_writer(&mut writer, &root)?;
+                writer.write_all(b"\n")?;
+            } else {
+                writer.write_all(if written == 0 { b"\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut writer, &root)?;
+            }
+            written += 1;
+        }
+        warn!("Exported {} documents", written);
+    }
+    if !ndjson {
+        writer.write_all(b"\n]\n")?;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Country name and attributes as they appear on a `Source` or `People`.
+struct CountryFields {
+    country: Option<String>,
+    geography: Option<String>,
+    orob: Option<String>,
+    orob_region: Option<String>,
+    geopolitics: Option<String>,
+}
+
+/// Read the `country`, `geography`, `belt_and_road`, `bri_since`,
+/// `orob_region` and `geopolitics` columns of a row joined with `country`.
+fn country_fields(row: &PgRow) -> CountryFields {
+    let orob = row.get::<Option<bool>, _>("belt_and_road").map(|member| Orob {
+        member,
+        since: row.get("bri_since"),
+    });
+    CountryFields {
+        country: row.get("country"),
+        geography: row.get("geography"),
+        orob: orob.map(|o| o.to_raw()),
+        orob_region: row.get("orob_region"),
+        geopolitics: row.get("geopolitics"),
+    }
+}
+
+/// Append `value` to a field holding several stored values, separated by `; `.
+fn push_value(field: &mut Option<String>, value: String) {
+    match field {
+        Some(existing) => {
+            existing.push_str("; ");
+            existing.push_str(&value);
+        }
+        None => *field = Some(value),
+    }
+}
+
+/// Reconstruct the documents of `(article_id, author_id)` pairs, in order.
+async fn documents(pool: &Pool<Postgres>, pairs: &[(i32, Option<i32>)]) -> Result<Vec<Root>> {
+    let mut article_ids: Vec<i32> = pairs.iter().map(|(a, _)| *a).collect();
+    article_ids.dedup();
+    let mut people_ids: Vec<i32> = pairs.iter().filter_map(|(_, p)| *p).collect();
+    people_ids.sort_unstable();
+    people_ids.dedup();
+
+    let mut articles = HashMap::new();
+    for row in sqlx::query(
+        "SELECT id, title, time, media_id, original_site, abstract, body, updated_at \
+        FROM article WHERE id = ANY($1)",
+    )
+    .bind(&article_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        let root = Root {
+            headline: row.get("title"),
+            time: row.get::<Option<DateTime<Utc>>, _>("time").map(|t| t.to_rfc3339()),
+            media_id: row.get("media_id"),
+            original_site: row.get("original_site"),
+            abstract_field: row.get("abstract"),
+            body: row.get("body"),
+            update_time: row.get::<Option<DateTime<Utc>>, _>("updated_at").map(|t| t.to_rfc3339()),
+            ..Default::default()
+        };
+        articles.insert(row.get::<i32, _>("id"), root);
+    }
+    for row in sqlx::query("SELECT article_id, stage, raw FROM article_pipeline_state WHERE article_id = ANY($1)")
+        .bind(&article_ids)
+        .fetch_all(pool)
+        .await?
+    {
+        if let (Some(root), Some(stage)) = (articles.get_mut(&row.get("article_id")), Stage::parse(row.get("stage"))) {
+            *root.pipeline_state_mut(stage) = row.get("raw");
+        }
+    }
+
+    let mut affiliations: HashMap<i32, Vec<(Affiliation, String)>> = HashMap::new();
+    for row in sqlx::query(
+        "SELECT source_id, kind, value FROM source_affiliation WHERE source_id IN \
+        (SELECT source_id FROM source_article WHERE article_id = ANY($1)) ORDER BY source_id, kind, value",
+    )
+    .bind(&article_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        if let Some(affiliation) = Affiliation::parse(row.get("kind")) {
+            affiliations
+                .entry(row.get("source_id"))
+                .or_default()
+                .push((affiliation, row.get("value")));
+        }
+    }
+    for row in sqlx::query(
+        "SELECT sa.article_id, s.id, s.name, c.name AS country, c.geography, c.belt_and_road, \
+        c.bri_since, c.orob_region, c.geopolitics FROM source_article sa \
+        JOIN source s ON s.id = sa.source_id LEFT JOIN country c ON c.id = s.country_id \
+        WHERE sa.article_id = ANY($1) ORDER BY sa.article_id, s.id",
+    )
+    .bind(&article_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        let country = country_fields(&row);
+        let mut source = Source {
+            name: row.get("name"),
+            country: country.country,
+            geography: country.geography,
+            orob: country.orob,
+            orob_region: country.orob_region,
+            geopolitics: country.geopolitics,
+            ..Default::default()
+        };
+        for (affiliation, value) in affiliations.remove(&row.get("id")).unwrap_or_default() {
+            push_value(source.affiliation_mut(affiliation), value);
+        }
+        if let Some(root) = articles.get_mut(&row.get("article_id")) {
+            root.source.push(source);
+        }
+    }
+
+    let mut people = HashMap::new();
+    for row in sqlx::query(
+        "SELECT p.id, p.name, p.title, c.name AS country, c.geography, c.belt_and_road, \
+        c.bri_since, c.orob_region, c.geopolitics FROM people p \
+        LEFT JOIN country c ON c.id = p.country_id WHERE p.id = ANY($1)",
+    )
+    .bind(&people_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        let country = country_fields(&row);
+        let person = People {
+            name: row.get("name"),
+            title: row.get("title"),
+            country: country.country,
+            geography: country.geography,
+            orob: country.orob,
+            orob_region: country.orob_region,
+            geopolitics: country.geopolitics,
+            ..Default::default()
+        };
+        people.insert(row.get::<i32, _>("id"), person);
+    }
+    for row in sqlx::query(
+        "SELECT pi.people_id, i.name, pi.value FROM people_identity pi \
+        JOIN identity i ON i.id = pi.identity_id WHERE pi.people_id = ANY($1) \
+        ORDER BY pi.people_id, i.name, pi.value",
+    )
+    .bind(&people_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        if let (Some(person), Some(identity)) = (people.get_mut(&row.get("people_id")), Identity::parse(row.get("name"))) {
+            push_value(person.identity_mut(identity), row.get("value"));
+        }
+    }
+    for row in sqlx::query(
+        "SELECT people_id, platform, account, url, screenshot FROM people_account \
+        WHERE people_id = ANY($1) ORDER BY people_id, platform, account",
+    )
+    .bind(&people_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        let Some(person) = people.get_mut(&row.get("people_id")) else {
+            continue;
+        };
+        let account: String = row.get("account");
+        let url: Option<String> = row.get("url");
+        // Only the first account of each platform has a field to go in
+        match Platform::parse(row.get("platform")) {
+            Some(Platform::Twitter) if person.twitter_acc.is_none() => {
+                person.twitter_acc = Some(account);
+                person.twitter = url;
+            }
+            Some(Platform::Facebook) if person.fb.is_none() => {
+                person.fb = url.or(Some(account));
+                person.fb_shot = row.get("screenshot");
+            }
+            Some(Platform::Youtube) if person.youtube_url.is_none() => {
+                person.youtube_url = url.or(Some(account));
+            }
+            _ => {}
+        }
+    }
+    for row in sqlx::query(
+        "SELECT people_id, chamber, state, district, party, period FROM legislator_term \
+        WHERE people_id = ANY($1) ORDER BY people_id, start_year NULLS LAST",
+    )
+    .bind(&people_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        let Some(person) = people.get_mut(&row.get("people_id")) else {
+            continue;
+        };
+        if person.from_congressman.is_none() && person.from_congressman_period.is_none() {
+            person.from_congressman = row.get("chamber");
+            person.from_congressman_state = row.get("state");
+            person.from_congressman_district = row.get("district");
+            person.from_congressman_party = row.get("party");
+            person.from_congressman_period = row.get("period");
+        }
+    }
+
+    let mut opinions: HashMap<(i32, i32), Vec<Opinion>> = HashMap::new();
+    for row in sqlx::query(
+        "SELECT article_id, author_id, text, score, span_start, span_end FROM opinion \
+        WHERE article_id = ANY($1) ORDER BY id",
+    )
+    .bind(&article_ids)
+    .fetch_all(pool)
+    .await?
+    {
+        opinions
+            .entry((row.get("article_id"), row.get("author_id")))
+            .or_default()
+            .push(Opinion {
+                score: row.get("score"),
+                start: row.get("span_start"),
+                end: row.get("span_end"),
+                text: row.get("text"),
+            });
+    }
+
+    Ok(pairs
+        .iter()
+        .filter_map(|&(article_id, author_id)| {
+            let mut root = articles.get(&article_id)?.clone();
+            if let Some(author_id) = author_id {
+                root.people = people.get(&author_id).cloned().unwrap_or_default();
+                root.people.opinion = opinions.remove(&(article_id, author_id)).unwrap_or_default();
+            }
+            Some(root)
+        })
+        .collect())
+}
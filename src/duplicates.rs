@@ -0,0 +1,117 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use log::warn;
+use rayon::prelude::*;
+use sqlx::{Pool, Postgres, Row};
+
+use crate::normalize::Normalizer;
+
+struct Candidate {
+    table: &'static str,
+    a: (i32, String),
+    b: (i32, String),
+    score: f64,
+}
+
+/// Scan `country`, `source` and `people` for names that likely refer to the
+/// same entity and write them with their similarity to a CSV for review.
+///
+/// Names are compared after every normalisation step. Only names sharing a
+/// two-character prefix or a last word are compared, so spelling variants of
+/// both given name and surname are caught without comparing every pair.
+/// People are only compared within a country, since the same name in two
+/// countries is kept as two people, and two reference countries are never
+/// compared with each other.
+pub async fn report(pool: &Pool<Postgres>, output: &Path, threshold: f64) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_path(output)?;
+    writer.write_record(["table", "id_a", "name_a", "id_b", "name_b", "score"])?;
+    for (table, block, reference) in [
+        ("country", "NULL::INT", "iso_alpha2 IS NOT NULL"),
+        ("source", "NULL::INT", "FALSE"),
+        ("people", "country_id", "FALSE"),
+    ] {
+        let rows: Vec<Entry> = sqlx::query(&format!(
+            "SELECT id, name, {} AS block, {} AS reference FROM {}",
+            block, reference, table
+        ))
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| Entry {
+            id: row.get("id"),
+            name: row.get("name"),
+            block: row.get("block"),
+            reference: row.get("reference"),
+        })
+        .collect();
+        let candidates = candidates(table, &rows, threshold);
+        warn!("{} duplicate candidates in {}", candidates.len(), table);
+        for c in candidates {
+            writer.write_record([
+                c.table.to_string(),
+                c.a.0.to_string(),
+                c.a.1,
+                c.b.0.to_string(),
+                c.b.1,
+                format!("{:.4}", c.score),
+            ])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// A row to compare. Only rows with the same `block` are compared.
+struct Entry {
+    id: i32,
+    name: String,
+    block: Option<i32>,
+    /// Bundled reference data, known to be distinct from other reference rows.
+    reference: bool,
+}
+
+fn candidates(table: &'static str, rows: &[Entry], threshold: f64) -> Vec<Candidate> {
+    let normalizer = Normalizer::all();
+    let keys: Vec<String> = rows.iter().map(|row| normalizer.apply(&row.name)).collect();
+
+    let mut blocks: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        let block = rows[i].block.map_or(String::new(), |b| b.to_string());
+        let prefix: String = key.chars().take(2).collect();
+        blocks.entry(format!("{}:p:{}", block, prefix)).or_default().push(i);
+        if let Some(last) = key.rsplit(' ').next().filter(|w| w.chars().count() > 2) {
+            blocks.entry(format!("{}:l:{}", block, last)).or_default().push(i);
+        }
+    }
+
+    let pairs: HashSet<(usize, usize)> = blocks
+        .par_iter()
+        .flat_map_iter(|(_, block)| {
+            let mut found = vec![];
+            for (n, &i) in block.iter().enumerate() {
+                for &j in &block[n + 1..] {
+                    if rows[i].reference && rows[j].reference {
+                        continue;
+                    }
+                    if strsim::jaro_winkler(&keys[i], &keys[j]) >= threshold {
+                        found.push((i.min(j), i.max(j)));
+                    }
+                }
+            }
+            found
+        })
+        .collect();
+
+    let mut candidates: Vec<Candidate> = pairs
+        .into_iter()
+        .map(|(i, j)| Candidate {
+            table,
+            a: (rows[i].id, rows[i].name.clone()),
+            b: (rows[j].id, rows[j].name.clone()),
+            score: strsim::jaro_winkler(&keys[i], &keys[j]),
+        })
+        .collect();
+    candidates.sort_by(|x, y| y.score.total_cmp(&x.score).then(x.a.0.cmp(&y.a.0)));
+    candidates
+}
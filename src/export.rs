@@ -0,0 +1,189 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use log::warn;
+use parquet::basic::Compression;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use sqlx::{Pool, Postgres, Row};
+
+use crate::table::{Kind, Table, Value, COLUMNS};
+
+/// Rows per Parquet row group.
+const ROW_GROUP: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Parquet,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Origin {
+    /// Parse the JSON files and assign ids in-process
+    Json,
+    /// Read the tables of the database at POSTGRES_URL
+    Postgres,
+}
+
+/// Read the exported tables from Postgres, ordered by their keys.
+pub async fn from_postgres(pool: &Pool<Postgres>) -> Result<Vec<Table>, sqlx::Error> {
+    let mut tables = vec![];
+    for (name, columns) in COLUMNS {
+        let mut table = Table::new(name);
+        let order = if *name == "source_article" { "source_id, article_id" } else { "id" };
+        let sql = format!(
+            "SELECT {} FROM {} ORDER BY {}",
+            columns.iter().map(|(c, _)| *c).collect::<Vec<_>>().join(", "),
+            name,
+            order,
+        );
+        for row in sqlx::query(&sql).fetch_all(pool).await? {
+            let mut values = Vec::with_capacity(columns.len());
+            for (i, (_, kind)) in columns.iter().enumerate() {
+                values.push(match kind {
+                    Kind::Int => row.try_get::<Option<i32>, _>(i)?.into(),
+                    Kind::BigInt => row.try_get::<Option<i64>, _>(i)?.into(),
+                    Kind::Float => row.try_get::<Option<f64>, _>(i)?.into(),
+                    Kind::Bool => row.try_get::<Option<bool>, _>(i)?.into(),
+                    Kind::Text => row.try_get::<Option<&str>, _>(i)?.into(),
+                    Kind::Date => row.try_get::<Option<NaiveDate>, _>(i)?.into(),
+                    Kind::Timestamp => row.try_get::<Option<DateTime<Utc>>, _>(i)?.into(),
+                });
+            }
+            table.rows.push(values);
+        }
+        tables.push(table);
+    }
+    Ok(tables)
+}
+
+/// Write `table` to `<dir>/<name>.csv` with a header. NULL is an empty field,
+/// dates are ISO 8601 and timestamps RFC 3339 in UTC.
+pub fn write_csv(table: &Table, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(dir.join(format!("{}.csv", table.name)))?;
+    writer.write_record(table.columns.iter().map(|(c, _)| *c))?;
+    for row in &table.rows {
+        writer.write_record(row.iter().map(|v| match v {
+            Value::Null => String::new(),
+            Value::Int(i) => i.to_string(),
+            Value::BigInt(i) => i.to_string(),
+            Value::Float(f) => f.to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Text(s) => s.clone(),
+            Value::Date(d) => d.to_string(),
+            Value::Timestamp(t) => t.to_rfc3339(),
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `table` to `<dir>/<name>.parquet`, Snappy compressed. Every column is
+/// optional; dates are `DATE` and timestamps UTC-adjusted `TIMESTAMP(MICROS)`.
+pub fn write_parquet(table: &Table, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let fields = table
+        .columns
+        .iter()
+        .map(|(c, kind)| {
+            let (physical, logical) = match kind {
+                Kind::Int => ("INT32", ""),
+                Kind::BigInt => ("INT64", ""),
+                Kind::Float => ("DOUBLE", ""),
+                Kind::Bool => ("BOOLEAN", ""),
+                Kind::Text => ("BINARY", " (STRING)"),
+                Kind::Date => ("INT32", " (DATE)"),
+                Kind::Timestamp => ("INT64", " (TIMESTAMP(MICROS,true))"),
+            };
+            format!("OPTIONAL {} {}{};", physical, c, logical)
+        })
+        .collect::<String>();
+    let schema = Arc::new(parse_message_type(&format!("message {} {{ {} }}", table.name, fields))?);
+    let props = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+    let file = File::create(dir.join(format!("{}.parquet", table.name)))?;
+    let mut writer = SerializedFileWriter::new(file, schema, props)?;
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+
+    for rows in table.rows.chunks(ROW_GROUP) {
+        let mut group = writer.next_row_group()?;
+        for (i, (_, kind)) in table.columns.iter().enumerate() {
+            let mut column = group.next_column()?.unwrap();
+            let defs: Vec<i16> = rows.iter().map(|row| i16::from(!row[i].is_null())).collect();
+            let values = rows.iter().map(|row| &row[i]);
+            match kind {
+                Kind::Int | Kind::Date => {
+                    let values: Vec<i32> = values
+                        .filter_map(|v| match v {
+                            Value::Int(i) => Some(*i),
+                            Value::Date(d) => Some((*d - epoch).num_days() as i32),
+                            _ => None,
+                        })
+                        .collect();
+                    column.typed::<Int32Type>().write_batch(&values, Some(&defs), None)?;
+                }
+                Kind::Bool => {
+                    let values: Vec<bool> = values
+                        .filter_map(|v| match v {
+                            Value::Bool(b) => Some(*b),
+                            _ => None,
+                        })
+                        .collect();
+                    column.typed::<BoolType>().write_batch(&values, Some(&defs), None)?;
+                }
+                Kind::Text => {
+                    let values: Vec<ByteArray> = values
+                        .filter_map(|v| match v {
+                            Value::Text(s) => Some(ByteArray::from(s.as_str())),
+                            _ => None,
+                        })
+                        .collect();
+                    column.typed::<ByteArrayType>().write_batch(&values, Some(&defs), None)?;
+                }
+                Kind::Float => {
+                    let values: Vec<f64> = values
+                        .filter_map(|v| match v {
+                            Value::Float(f) => Some(*f),
+                            _ => None,
+                        })
+                        .collect();
+                    column.typed::<DoubleType>().write_batch(&values, Some(&defs), None)?;
+                }
+                Kind::BigInt | Kind::Timestamp => {
+                    let values: Vec<i64> = values
+                        .filter_map(|v| match v {
+                            Value::BigInt(i) => Some(*i),
+                            Value::Timestamp(t) => Some(t.timestamp_micros()),
+                            _ => None,
+                        })
+                        .collect();
+                    column.typed::<Int64Type>().write_batch(&values, Some(&defs), None)?;
+                }
+            }
+            column.close()?;
+        }
+        group.close()?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// Write every table in each of `formats` under `dir`, creating it if needed.
+pub fn write(tables: &[Table], dir: &Path, formats: &[Format]) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    for table in tables {
+        warn!("Exporting {} rows of {}", table.rows.len(), table.name);
+        for format in formats {
+            match format {
+                Format::Parquet => write_parquet(table, dir)?,
+                Format::Csv => write_csv(table, dir)?,
+            }
+        }
+    }
+    Ok(())
+}
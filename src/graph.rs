@@ -0,0 +1,428 @@
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use log::warn;
+
+use crate::table::{Kind, Table, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Graphml,
+    Gexf,
+    /// Node and relationship CSV files for `neo4j-admin database import`
+    Neo4j,
+}
+
+/// Node labels and their properties.
+pub const NODES: &[(&str, &[(&str, Kind)])] = &[
+    ("Person", &[("name", Kind::Text), ("title", Kind::Text)]),
+    ("Source", &[("name", Kind::Text)]),
+    (
+        "Article",
+        &[
+            ("title", Kind::Text),
+            ("time", Kind::Timestamp),
+            ("media_id", Kind::Text),
+            ("original_site", Kind::Text),
+        ],
+    ),
+    (
+        "Country",
+        &[
+            ("name", Kind::Text),
+            ("iso_alpha2", Kind::Text),
+            ("iso_alpha3", Kind::Text),
+            ("region", Kind::Text),
+        ],
+    ),
+];
+
+/// Relationship types and their properties. `time` is the article's.
+pub const EDGES: &[(&str, &[(&str, Kind)])] = &[
+    (
+        "SAID_IN",
+        &[("opinion_id", Kind::Int), ("score", Kind::Float), ("time", Kind::Timestamp)],
+    ),
+    ("PUBLISHED", &[("time", Kind::Timestamp)]),
+    ("FROM_COUNTRY", &[]),
+];
+
+/// A node or relationship with the values of its label's properties, in order.
+pub struct Element {
+    pub label: &'static str,
+    pub values: Vec<Value>,
+}
+
+pub struct Node {
+    /// `Label:id`, unique across labels.
+    pub id: String,
+    pub element: Element,
+}
+
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub element: Element,
+}
+
+/// People, sources, articles and the countries they are from, with opinions
+/// as `SAID_IN` edges from people to articles.
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+fn properties(label: &str) -> &'static [(&'static str, Kind)] {
+    NODES
+        .iter()
+        .chain(EDGES)
+        .find(|(l, _)| *l == label)
+        .unwrap_or_else(|| panic!("Unknown label: {}", label))
+        .1
+}
+
+fn node_id(label: &str, id: &Value) -> Option<String> {
+    match id {
+        Value::Int(id) => Some(format!("{}:{}", label, id)),
+        _ => None,
+    }
+}
+
+impl Graph {
+    /// Build the graph from the exported tables. Only countries someone is from become nodes.
+    pub fn from_tables(tables: &[Table]) -> Graph {
+        let table = |name: &str| {
+            tables
+                .iter()
+                .find(|t| t.name == name)
+                .unwrap_or_else(|| panic!("Missing table: {}", name))
+        };
+        let (country, source, people, article) = (table("country"), table("source"), table("people"), table("article"));
+        let mut graph = Graph { nodes: vec![], edges: vec![] };
+
+        // Copy the label's properties out of a row of `table`
+        let element = |label, table: &Table, row: &[Value]| Element {
+            label,
+            values: properties(label).iter().map(|(c, _)| row[table.column(c)].clone()).collect(),
+        };
+
+        let mut countries = BTreeSet::new();
+        for (label, table) in [("Person", people), ("Source", source)] {
+            for row in &table.rows {
+                let id = node_id(label, &row[0]).unwrap();
+                if let Value::Int(country_id) = row[table.column("country_id")] {
+                    countries.insert(country_id);
+                    graph.edges.push(Edge {
+                        source: id.clone(),
+                        target: format!("Country:{}", country_id),
+                        element: Element { label: "FROM_COUNTRY", values: vec![] },
+                    });
+                }
+                graph.nodes.push(Node { id, element: element(label, table, row) });
+            }
+        }
+        let mut times = HashMap::new();
+        for row in &article.rows {
+            times.insert(node_id("Article", &row[0]).unwrap(), row[article.column("time")].clone());
+            graph.nodes.push(Node {
+                id: node_id("Article", &row[0]).unwrap(),
+                element: element("Article", article, row),
+            });
+        }
+        for row in &country.rows {
+            if matches!(row[0], Value::Int(id) if countries.contains(&id)) {
+                graph.nodes.push(Node {
+                    id: node_id("Country", &row[0]).unwrap(),
+                    element: element("Country", country, row),
+                });
+            }
+        }
+
+        // Edges to articles missing from the export would dangle, so they are left out
+        let mut dangling = 0;
+        let links = table("source_article");
+        for row in &links.rows {
+            let target = node_id("Article", &row[links.column("article_id")]).unwrap();
+            let Some(time) = times.get(&target) else {
+                dangling += 1;
+                continue;
+            };
+            graph.edges.push(Edge {
+                source: node_id("Source", &row[links.column("source_id")]).unwrap(),
+                element: Element { label: "PUBLISHED", values: vec![time.clone()] },
+                target,
+            });
+        }
+        let opinion = table("opinion");
+        for row in &opinion.rows {
+            let target = node_id("Article", &row[opinion.column("article_id")]).unwrap();
+            let Some(time) = times.get(&target) else {
+                dangling += 1;
+                continue;
+            };
+            graph.edges.push(Edge {
+                source: node_id("Person", &row[opinion.column("author_id")]).unwrap(),
+                element: Element {
+                    label: "SAID_IN",
+                    values: vec![row[0].clone(), row[opinion.column("score")].clone(), time.clone()],
+                },
+                target,
+            });
+        }
+        if dangling > 0 {
+            warn!("Left out {} edges to articles that are not in the graph", dangling);
+        }
+        graph
+    }
+}
+
+/// The value as written into the files, `None` for NULL.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Int(i) => Some(i.to_string()),
+        Value::BigInt(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Text(s) => Some(s.clone()),
+        Value::Date(d) => Some(d.to_string()),
+        Value::Timestamp(t) => Some(t.to_rfc3339()),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        // Control characters other than tab and newlines are not allowed in XML 1.0
+        .replace(|c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r'), "")
+}
+
+/// Every property of nodes or edges across labels, once, with its kind.
+fn union(labels: &[(&'static str, &'static [(&'static str, Kind)])]) -> Vec<(&'static str, Kind)> {
+    let mut all: Vec<(&str, Kind)> = vec![];
+    for (_, properties) in labels {
+        for &(name, kind) in *properties {
+            if !all.iter().any(|(n, _)| *n == name) {
+                all.push((name, kind));
+            }
+        }
+    }
+    all
+}
+
+/// Pairs of property name and value present on `element`.
+fn values(element: &Element) -> impl Iterator<Item = (&'static str, String)> + '_ {
+    properties(element.label)
+        .iter()
+        .zip(&element.values)
+        .filter_map(|((name, _), value)| text(value).map(|v| (*name, v)))
+}
+
+pub fn write_graphml(graph: &Graph, path: &Path) -> Result<(), Box<dyn Error>> {
+    let kind = |kind: Kind| match kind {
+        Kind::Int => "int",
+        Kind::BigInt => "long",
+        Kind::Float => "double",
+        Kind::Bool => "boolean",
+        Kind::Text | Kind::Date | Kind::Timestamp => "string",
+    };
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+    for (class, labels, prefix) in [("node", NODES, "n"), ("edge", EDGES, "e")] {
+        writeln!(w, r#"  <key id="{p}_label" for="{c}" attr.name="label" attr.type="string"/>"#, p = prefix, c = class)?;
+        for (name, k) in union(labels) {
+            writeln!(
+                w,
+                r#"  <key id="{p}_{n}" for="{c}" attr.name="{n}" attr.type="{t}"/>"#,
+                p = prefix,
+                n = name,
+                c = class,
+                t = kind(k),
+            )?;
+        }
+    }
+    writeln!(w, r#"  <graph id="G" edgedefault="directed">"#)?;
+    for node in &graph.nodes {
+        write!(w, r#"    <node id="{}"><data key="n_label">{}</data>"#, escape(&node.id), node.element.label)?;
+        for (name, value) in values(&node.element) {
+            write!(w, r#"<data key="n_{}">{}</data>"#, name, escape(&value))?;
+        }
+        writeln!(w, "</node>")?;
+    }
+    for (i, edge) in graph.edges.iter().enumerate() {
+        write!(
+            w,
+            r#"    <edge id="e{}" source="{}" target="{}"><data key="e_label">{}</data>"#,
+            i,
+            escape(&edge.source),
+            escape(&edge.target),
+            edge.element.label,
+        )?;
+        for (name, value) in values(&edge.element) {
+            write!(w, r#"<data key="e_{}">{}</data>"#, name, escape(&value))?;
+        }
+        writeln!(w, "</edge>")?;
+    }
+    writeln!(w, "  </graph>")?;
+    writeln!(w, "</graphml>")?;
+    w.flush()?;
+    Ok(())
+}
+
+pub fn write_gexf(graph: &Graph, path: &Path) -> Result<(), Box<dyn Error>> {
+    let kind = |kind: Kind| match kind {
+        Kind::Int => "integer",
+        Kind::BigInt => "long",
+        Kind::Float => "double",
+        Kind::Bool => "boolean",
+        Kind::Text | Kind::Date | Kind::Timestamp => "string",
+    };
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#)?;
+    writeln!(w, r#"  <graph mode="static" defaultedgetype="directed">"#)?;
+    for (class, labels) in [("node", NODES), ("edge", EDGES)] {
+        writeln!(w, r#"    <attributes class="{}">"#, class)?;
+        writeln!(w, r#"      <attribute id="type" title="type" type="string"/>"#)?;
+        for (name, k) in union(labels) {
+            writeln!(w, r#"      <attribute id="{n}" title="{n}" type="{t}"/>"#, n = name, t = kind(k))?;
+        }
+        writeln!(w, "    </attributes>")?;
+    }
+    writeln!(w, "    <nodes>")?;
+    for node in &graph.nodes {
+        // The first property, a name or title, is the display label
+        let label = node.element.values.first().and_then(text).unwrap_or_else(|| node.id.clone());
+        write!(w, r#"      <node id="{}" label="{}"><attvalues>"#, escape(&node.id), escape(&label))?;
+        write!(w, r#"<attvalue for="type" value="{}"/>"#, node.element.label)?;
+        for (name, value) in values(&node.element) {
+            write!(w, r#"<attvalue for="{}" value="{}"/>"#, name, escape(&value))?;
+        }
+        writeln!(w, "</attvalues></node>")?;
+    }
+    writeln!(w, "    </nodes>")?;
+    writeln!(w, "    <edges>")?;
+    for (i, edge) in graph.edges.iter().enumerate() {
+        write!(
+            w,
+            r#"      <edge id="{}" source="{}" target="{}" label="{l}"><attvalues><attvalue for="type" value="{l}"/>"#,
+            i,
+            escape(&edge.source),
+            escape(&edge.target),
+            l = edge.element.label,
+        )?;
+        for (name, value) in values(&edge.element) {
+            write!(w, r#"<attvalue for="{}" value="{}"/>"#, name, escape(&value))?;
+        }
+        writeln!(w, "</attvalues></edge>")?;
+    }
+    writeln!(w, "    </edges>")?;
+    writeln!(w, "  </graph>")?;
+    writeln!(w, "</gexf>")?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Write `nodes_<Label>.csv` and `relationships_<TYPE>.csv` under `dir` with
+/// the typed headers of `neo4j-admin database import`, returning the import
+/// arguments naming them.
+pub fn write_neo4j(graph: &Graph, dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let kind = |kind: Kind| match kind {
+        Kind::Int => ":int",
+        Kind::BigInt => ":long",
+        Kind::Float => ":double",
+        Kind::Bool => ":boolean",
+        Kind::Text => "",
+        Kind::Date => ":date",
+        Kind::Timestamp => ":datetime",
+    };
+    let mut arguments = vec![];
+    for (label, properties) in NODES {
+        let file = format!("nodes_{}.csv", label);
+        let mut writer = csv::Writer::from_path(dir.join(&file))?;
+        let mut header = vec!["id:ID".to_string()];
+        header.extend(properties.iter().map(|(name, k)| format!("{}{}", name, kind(*k))));
+        header.push(":LABEL".to_string());
+        writer.write_record(&header)?;
+        for node in graph.nodes.iter().filter(|n| n.element.label == *label) {
+            let mut record = vec![node.id.clone()];
+            record.extend(node.element.values.iter().map(|v| text(v).unwrap_or_default()));
+            record.push(label.to_string());
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        arguments.push(format!("--nodes={}", file));
+    }
+    for (label, properties) in EDGES {
+        let file = format!("relationships_{}.csv", label);
+        let mut writer = csv::Writer::from_path(dir.join(&file))?;
+        let mut header = vec![":START_ID".to_string(), ":END_ID".to_string()];
+        header.extend(properties.iter().map(|(name, k)| format!("{}{}", name, kind(*k))));
+        header.push(":TYPE".to_string());
+        writer.write_record(&header)?;
+        for edge in graph.edges.iter().filter(|e| e.element.label == *label) {
+            let mut record = vec![edge.source.clone(), edge.target.clone()];
+            record.extend(edge.element.values.iter().map(|v| text(v).unwrap_or_default()));
+            record.push(label.to_string());
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        arguments.push(format!("--relationships={}", file));
+    }
+    Ok(arguments)
+}
+
+/// Write the graph in each of `formats` under `dir`, creating it if needed.
+pub fn write(graph: &Graph, dir: &Path, formats: &[Format]) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    warn!("Exporting {} nodes and {} edges", graph.nodes.len(), graph.edges.len());
+    for format in formats {
+        match format {
+            Format::Graphml => write_graphml(graph, &dir.join("graph.graphml"))?,
+            Format::Gexf => write_gexf(graph, &dir.join("graph.gexf"))?,
+            Format::Neo4j => {
+                let arguments = write_neo4j(graph, dir)?;
+                warn!(
+                    "Import into Neo4j from {} with: neo4j-admin database import full {}",
+                    dir.display(),
+                    arguments.join(" ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_to_missing_articles_are_left_out() {
+        let mut article = Table::new("article");
+        let mut row = vec![Value::Null; article.columns.len()];
+        row[0] = Value::Int(1);
+        article.rows.push(row);
+        let mut links = Table::new("source_article");
+        links.rows = vec![vec![Value::Int(1), Value::Int(1)], vec![Value::Int(1), Value::Int(2)]];
+        let mut opinion = Table::new("opinion");
+        for article_id in [1, 2] {
+            let mut row = vec![Value::Null; opinion.columns.len()];
+            row[0] = Value::Int(article_id);
+            row[opinion.column("author_id")] = Value::Int(1);
+            row[opinion.column("article_id")] = Value::Int(article_id);
+            opinion.rows.push(row);
+        }
+        let tables = [Table::new("people"), Table::new("source"), Table::new("country"), article, links, opinion];
+        let graph = Graph::from_tables(&tables);
+        let targets: Vec<_> = graph.edges.iter().map(|e| (e.element.label, e.target.as_str())).collect();
+        assert_eq!(targets, vec![("PUBLISHED", "Article:1"), ("SAID_IN", "Article:1")]);
+    }
+}
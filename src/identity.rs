@@ -0,0 +1,64 @@
+/// Identity categories carried by the `Identity_*` fields of `People`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Identity {
+    Entertainment,
+    Refugee,
+    Crime,
+    Military,
+    Business,
+    Expert,
+    Media,
+    Religion,
+    Activist,
+    Politician,
+    Judge,
+    Student,
+    Terrorist,
+    Sports,
+    Lawyer,
+}
+
+impl Identity {
+    pub const ALL: [Identity; 15] = [
+        Identity::Entertainment,
+        Identity::Refugee,
+        Identity::Crime,
+        Identity::Military,
+        Identity::Business,
+        Identity::Expert,
+        Identity::Media,
+        Identity::Religion,
+        Identity::Activist,
+        Identity::Politician,
+        Identity::Judge,
+        Identity::Student,
+        Identity::Terrorist,
+        Identity::Sports,
+        Identity::Lawyer,
+    ];
+
+    /// The identity stored as `name` in the `identity` table.
+    pub fn parse(name: &str) -> Option<Identity> {
+        Identity::ALL.into_iter().find(|i| i.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Identity::Entertainment => "entertainment",
+            Identity::Refugee => "refugee",
+            Identity::Crime => "crime",
+            Identity::Military => "military",
+            Identity::Business => "business",
+            Identity::Expert => "expert",
+            Identity::Media => "media",
+            Identity::Religion => "religion",
+            Identity::Activist => "activist",
+            Identity::Politician => "politician",
+            Identity::Judge => "judge",
+            Identity::Student => "student",
+            Identity::Terrorist => "terrorist",
+            Identity::Sports => "sports",
+            Identity::Lawyer => "lawyer",
+        }
+    }
+}
@@ -0,0 +1,37 @@
+/// A congress membership taken from the `From_Congressman*` fields of `People`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term<'a> {
+    pub chamber: Option<&'a str>,
+    pub state: Option<&'a str>,
+    pub district: Option<&'a str>,
+    pub party: Option<&'a str>,
+    pub period: Option<&'a str>,
+    pub start_year: Option<i32>,
+    pub end_year: Option<i32>,
+}
+
+/// Parse the years out of periods like `2019-2021`, `2017 – present` or `2013`.
+///
+/// A single year is taken as both start and end, an open period leaves the
+/// end empty and the literal `None` yields no period at all.
+pub fn parse_period(period: &str) -> (Option<i32>, Option<i32>) {
+    let period = period.trim();
+    if period.is_empty() || period.eq_ignore_ascii_case("none") {
+        return (None, None);
+    }
+    let years: Vec<i32> = period
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| s.len() == 4)
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    let lower = period.to_lowercase();
+    let open = ["present", "now", "incumbent", "current", "至今"]
+        .iter()
+        .any(|s| lower.contains(s));
+    match years.as_slice() {
+        [] => (None, None),
+        [year] if open => (Some(*year), None),
+        [year] => (Some(*year), Some(*year)),
+        [start, .., end] => (Some(*start), Some(*end)),
+    }
+}
@@ -1,124 +1,395 @@
 #![feature(result_option_inspect)]
 
+mod account;
+mod admin;
+mod affiliation;
+mod article;
+mod copy;
+mod corpus;
+mod country;
+mod documents;
+mod duplicates;
+mod export;
+mod graph;
+mod identity;
+mod legislator;
+mod memory;
+mod mysql;
+mod normalize;
+mod offsets;
+mod person;
+mod pipeline;
+mod postgres;
+mod raw;
+mod rules;
 mod schema;
+mod search;
+mod sink;
+mod sqlite;
+mod table;
+mod timestamp;
+mod upsert;
 
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use std::fmt::Write;
 
-use backoff::future::retry;
-use backoff::ExponentialBackoff;
+use chrono::{DateTime, NaiveDate, Utc};
 use sqlx::postgres::PgPoolOptions;
-use sqlx::{Error, Pool, Postgres, Row};
+use sqlx::{Pool, Postgres};
 use std::fs::File;
 
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use crate::admin::Entity;
+use crate::article::{ArticleKey, MissingHeadline};
+use crate::country::{parse_orob, Attributes, Reference};
+use crate::export::{Format, Origin};
+use crate::memory::MemorySink;
+use crate::normalize::Normalizer;
+use crate::mysql::MySqlSink;
+use crate::pipeline::Status;
+use crate::postgres::{create_tables, PgSink};
+use crate::rules::Rules;
 use crate::schema::Root;
+use crate::sink::Sink;
+use crate::sqlite::SqliteSink;
+use crate::timestamp::TimeParser;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
-use log::{error, info, warn};
+use log::{info, warn};
+
+#[derive(Parser)]
+#[command(about = "Migrate annotated news records into Postgres")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Load every JSON file in ./data_new (the default)
+    Migrate {
+        /// Also write the records to this SQLite file
+        #[arg(long)]
+        sqlite: Option<PathBuf>,
+        /// Also write the records to the MySQL database at MYSQL_URL
+        #[arg(long)]
+        mysql: bool,
+    },
+    /// Write likely duplicate country, source and people names to a CSV for review
+    Duplicates {
+        #[arg(default_value = "duplicates.csv")]
+        output: PathBuf,
+        /// Minimum Jaro-Winkler similarity of the normalised names
+        #[arg(long, default_value_t = 0.92)]
+        threshold: f64,
+    },
+    /// Merge two rows of the same entity, repointing everything at the kept one
+    Merge {
+        entity: Entity,
+        keep_id: i32,
+        drop_id: i32,
+    },
+    /// Move opinions of a person, or articles of a source, to another row
+    Split {
+        entity: Entity,
+        from_id: i32,
+        /// Existing row to move to
+        #[arg(long)]
+        to: Option<i32>,
+        /// Name of a new row to create and move to
+        #[arg(long)]
+        name: Option<String>,
+        /// Opinion ids for people, article ids for sources
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Write the records of a data folder to a self-contained SQLite file
+    Sqlite {
+        #[arg(default_value = "delivery.sqlite")]
+        output: PathBuf,
+        /// Folder of JSON files to read
+        #[arg(long, default_value = "./data_new")]
+        data: PathBuf,
+    },
+    /// Write the records of a data folder to the MySQL database at MYSQL_URL
+    Mysql {
+        /// Folder of JSON files to read
+        #[arg(long, default_value = "./data_new")]
+        data: PathBuf,
+    },
+    /// Write the core tables as Parquet and CSV files
+    Export {
+        #[arg(default_value = "export")]
+        output: PathBuf,
+        /// Where the rows come from
+        #[arg(long, value_enum, default_value_t = Origin::Json)]
+        from: Origin,
+        /// Folder of JSON files to read with `--from json`
+        #[arg(long, default_value = "./data_new")]
+        data: PathBuf,
+        /// Formats to write, both by default
+        #[arg(long, value_enum, default_values_t = [Format::Parquet, Format::Csv])]
+        format: Vec<Format>,
+    },
+    /// Write the people, source, article and country network as a graph
+    Graph {
+        #[arg(default_value = "graph")]
+        output: PathBuf,
+        /// Where the rows come from
+        #[arg(long, value_enum, default_value_t = Origin::Json)]
+        from: Origin,
+        /// Folder of JSON files to read with `--from json`
+        #[arg(long, default_value = "./data_new")]
+        data: PathBuf,
+        /// Formats to write, all by default
+        #[arg(long, value_enum, default_values_t = [graph::Format::Graphml, graph::Format::Gexf, graph::Format::Neo4j])]
+        format: Vec<graph::Format>,
+    },
+    /// Write article bodies with their opinion spans as NLP training data
+    Corpus {
+        #[arg(default_value = "corpus")]
+        output: PathBuf,
+        /// Where the rows come from
+        #[arg(long, value_enum, default_value_t = Origin::Json)]
+        from: Origin,
+        /// Folder of JSON files to read with `--from json`
+        #[arg(long, default_value = "./data_new")]
+        data: PathBuf,
+        /// Formats to write, all by default
+        #[arg(long, value_enum, default_values_t = [corpus::Format::Spacy, corpus::Format::Conll, corpus::Format::Jsonl])]
+        format: Vec<corpus::Format>,
+        /// Scores within this distance of zero are labelled NEUTRAL
+        #[arg(long, default_value_t = 0.05)]
+        neutral: f64,
+        /// Fraction of each source's documents in the dev split
+        #[arg(long, default_value_t = 0.1)]
+        dev: f64,
+        /// Fraction of each source's documents in the test split
+        #[arg(long, default_value_t = 0.1)]
+        test: f64,
+        /// Changes which documents land in each split
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Check opinion offsets against article bodies and rewrite them as char offsets
+    ValidateSpans {
+        /// CSV of the opinions that do not match in their file's offset convention
+        #[arg(default_value = "spans.csv")]
+        report: PathBuf,
+        /// Folder of JSON files to read
+        #[arg(long, default_value = "./data_new")]
+        data: PathBuf,
+        /// Folder to write the files to with repaired offsets
+        #[arg(long)]
+        write: Option<PathBuf>,
+    },
+    /// Write the records in the database back out as `Root` JSON documents
+    ExportJson {
+        #[arg(default_value = "documents.json")]
+        output: PathBuf,
+        /// Write one document per line instead of a JSON array
+        #[arg(long)]
+        ndjson: bool,
+        /// First day of the article time, inclusive
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Last day of the article time, inclusive
+        #[arg(long)]
+        until: Option<NaiveDate>,
+        /// Country name or ISO code of the person or a source, repeatable
+        #[arg(long)]
+        country: Vec<String>,
+        /// Source name, repeatable
+        #[arg(long)]
+        source: Vec<String>,
+        /// Person name or alias, repeatable
+        #[arg(long)]
+        person: Vec<String>,
+    },
+    /// Copy the tables of another Postgres database into the one at POSTGRES_URL,
+    /// merging rows that exist in both
+    Copy {
+        /// Connection URL of the database to read from
+        from: String,
+    },
+    /// Rebuild the normalised tables from the archived raw records
+    Replay {
+        /// Empty the tables derived from records first
+        #[arg(long)]
+        truncate: bool,
+    },
+    /// Print articles and opinions matching a web search style query, best first
+    Search {
+        query: String,
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+}
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() -> sink::Result<()> {
     dotenv().ok();
     env_logger::builder().filter_level(log::LevelFilter::Warn).init();
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Sqlite { output, data }) => {
+            load(&[Box::new(SqliteSink::open(output).unwrap())], data).await?;
+            return Ok(());
+        }
+        Some(Command::Mysql { data }) => {
+            load(&[Box::new(MySqlSink::connect().await?)], data).await?;
+            return Ok(());
+        }
+        Some(Command::Export { output, from: Origin::Json, data, format }) => {
+            export::write(&json_tables(data).await?, output, format).unwrap();
+            return Ok(());
+        }
+        Some(Command::Corpus { output, from: Origin::Json, data, format, neutral, dev, test, seed }) => {
+            let documents = corpus::documents(&json_tables(data).await?, *neutral);
+            let splits = corpus::split(&documents, *dev, *test, *seed);
+            corpus::write(&documents, &splits, output, format).unwrap();
+            return Ok(());
+        }
+        Some(Command::Graph { output, from: Origin::Json, data, format }) => {
+            let graph = graph::Graph::from_tables(&json_tables(data).await?);
+            graph::write(&graph, output, format).unwrap();
+            return Ok(());
+        }
+        Some(Command::ValidateSpans { report, data, write }) => {
+            let files = data_files(data).into_iter().map(|path| {
+                let records = read_records(&path);
+                (path, records)
+            });
+            offsets::validate(data, files.collect(), report, write.as_deref()).unwrap();
+            return Ok(());
+        }
+        _ => {}
+    }
 
     let url = std::env::var("POSTGRES_URL").expect("POSTGRES_URL must be set.");
     let pool = PgPoolOptions::new()
         .max_connections(400)
         .connect(&url)
         .await?;
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS country (
-        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
-        name TEXT UNIQUE NOT NULL,
-        geography TEXT,
-        belt_and_road BOOLEAN
-    )",
-    ).execute(&pool).await?;
-
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS people (
-        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
-        name TEXT UNIQUE NOT NULL,
-        country_id INT,
-        title TEXT,
-        origin TEXT,
-        identity TEXT,
-        CONSTRAINT fk_country
-            FOREIGN KEY (country_id)
-            REFERENCES country(id)
-            ON DELETE CASCADE
-    );",
-    )
+    match cli.command.unwrap_or(Command::Migrate { sqlite: None, mysql: false }) {
+        Command::Migrate { sqlite, mysql } => migrate(&pool, sqlite.as_deref(), mysql).await?,
+        Command::Replay { truncate } => replay(&pool, truncate).await?,
+        Command::Copy { from } => {
+            create_tables(&pool).await?;
+            let source = PgPoolOptions::new().max_connections(4).connect(&from).await?;
+            copy::copy(&source, &pool).await?
+        }
+        Command::Sqlite { .. } | Command::Mysql { .. } | Command::ValidateSpans { .. } => unreachable!(),
+        Command::Export { output, format, .. } => {
+            let tables = export::from_postgres(&pool).await?;
+            export::write(&tables, &output, &format).unwrap()
+        }
+        Command::Corpus { output, format, neutral, dev, test, seed, .. } => {
+            let documents = corpus::documents(&export::from_postgres(&pool).await?, neutral);
+            let splits = corpus::split(&documents, dev, test, seed);
+            corpus::write(&documents, &splits, &output, &format).unwrap()
+        }
+        Command::Graph { output, format, .. } => {
+            let graph = graph::Graph::from_tables(&export::from_postgres(&pool).await?);
+            graph::write(&graph, &output, &format).unwrap()
+        }
+        Command::Duplicates { output, threshold } => duplicates::report(&pool, &output, threshold).await.unwrap(),
+        Command::Merge { entity, keep_id, drop_id } => {
+            create_tables(&pool).await?;
+            admin::merge(&pool, entity, keep_id, drop_id).await.unwrap()
+        }
+        Command::Split { entity, from_id, to, name, ids } => {
+            create_tables(&pool).await?;
+            admin::split(&pool, entity, from_id, to, name.as_deref(), &ids).await.unwrap()
+        }
+        Command::ExportJson { output, ndjson, since, until, country, source, person } => {
+            create_tables(&pool).await?;
+            let filter = documents::Filter { since, until, countries: country, sources: source, people: person };
+            let written = documents::export(&pool, &filter, &output, ndjson).await.unwrap();
+            warn!("Wrote {} documents to {}", written, output.display());
+        }
+        Command::Search { query, limit } => {
+            create_tables(&pool).await?;
+            search::search(&pool, &query, &search::config_from_env(), limit).await.unwrap()
+        }
+    }
+    Ok(())
+}
 
-    .execute(&pool)
-    .await?;
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS source (
-        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
-        name TEXT UNIQUE NOT NULL,
-        country_id INT,
-        origin TEXT,
-        CONSTRAINT fk_country
-            FOREIGN KEY (country_id)
-            REFERENCES country(id)
-            ON DELETE CASCADE
-    );",
-    )
-    .execute(&pool)
-    .await?;
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS article (
-        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
-        title TEXT UNIQUE NOT NULL,
-        time INT NOT NULL
-    );",
-    )
-    .execute(&pool)
-    .await?;
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS source_article (\
-        source_id INT NOT NULL,
-        article_id INT NOT NULL,
-        UNIQUE (source_id, article_id),
-        CONSTRAINT fk_source
-            FOREIGN KEY (source_id)
-            REFERENCES source(id)
-            ON DELETE CASCADE,
-        CONSTRAINT fk_article
-            FOREIGN KEY (article_id)
-            REFERENCES article(id)
-            ON DELETE CASCADE
-    );",
-    )
-    .execute(&pool)
-    .await?;
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS opinion (
-        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
-        author_id INT NOT NULL,
-        text TEXT UNIQUE NOT NULL,
-        article_id INT NOT NULL,
-        CONSTRAINT fk_author
-            FOREIGN KEY (author_id)
-            REFERENCES people(id)
-            ON DELETE CASCADE,
-        CONSTRAINT fk_article
-            FOREIGN KEY (article_id)
-            REFERENCES article(id)
-            ON DELETE CASCADE
-    );",
-    )
-    .execute(&pool)
-    .await?;
+async fn migrate(pool: &Pool<Postgres>, sqlite: Option<&Path>, mysql: bool) -> sink::Result<()> {
+    let context = Context::from_env();
+    let files = data_files(Path::new("./data_new"));
+    check_aborts(&context, &files)?;
+    create_tables(pool).await?;
+    let mut sinks: Vec<Box<dyn Sink>> = vec![Box::new(PgSink::new(pool.clone()))];
+    if let Some(path) = sqlite {
+        sinks.push(Box::new(SqliteSink::open(path).unwrap()));
+    }
+    if mysql {
+        sinks.push(Box::new(MySqlSink::connect().await?));
+    }
 
     // Iterate over file in data folder
     warn!("Start processing files in data folder");
-    let data_dir = Path::new("./data_new");
-    let iter = data_dir
+    let run_id = raw::start_run(pool).await?;
+    for path in files {
+        warn!("Start processing file: {}", path.to_str().unwrap());
+        let records = read_records(&path);
+        let archived = raw::archive(pool, run_id, path.to_str().unwrap(), &records).await?;
+        warn!("Archived {} new of {} records", archived, records.len());
+        process_roots(&sinks, &context, raw::roots(records)).await.unwrap();
+    }
+    for sink in &sinks {
+        sink.finish().await.unwrap();
+    }
+    raw::finish_run(pool, run_id).await?;
+    Ok(())
+}
+
+/// Write every file in `data_dir` to `sinks` only, without Postgres.
+async fn load(sinks: &[Box<dyn Sink>], data_dir: &Path) -> sink::Result<()> {
+    let context = Context::from_env();
+    let files = data_files(data_dir);
+    check_aborts(&context, &files)?;
+    for path in files {
+        warn!("Start processing file: {}", path.to_str().unwrap());
+        process_roots(sinks, &context, raw::roots(read_records(&path))).await.unwrap();
+    }
+    for sink in sinks {
+        sink.finish().await.unwrap();
+    }
+    Ok(())
+}
+
+/// The exported tables of the records in `data_dir`, with ids assigned in-process.
+async fn json_tables(data_dir: &Path) -> sink::Result<Vec<table::Table>> {
+    let sink = MemorySink::new();
+    load(&[Box::new(sink.clone())], data_dir).await?;
+    Ok(sink.tables())
+}
+
+/// Check the records of every file against the abort rules before any is
+/// loaded, so that breaking one stops the run without writing anything.
+fn check_aborts(context: &Context, files: &[PathBuf]) -> sink::Result<()> {
+    if !context.rules.aborts() {
+        return Ok(());
+    }
+    let failed = files
+        .iter()
+        .filter(|path| context.rules.check_aborts(&raw::roots(read_records(path)), &context.times).is_err())
+        .count();
+    if failed > 0 {
+        return Err(format!("Records in {} files break abort rules, nothing was loaded", failed).into());
+    }
+    Ok(())
+}
+
+fn data_files(data_dir: &Path) -> Vec<PathBuf> {
+    data_dir
         .read_dir()
         .unwrap()
         .filter_map(|entry| {
@@ -128,18 +399,85 @@ async fn main() -> Result<(), Error> {
             } else {
                 None
             }
-        });
-    for path in iter {
-        let file = File::open(&path).unwrap();
-        let reader = BufReader::new(file);
-        warn!("Start processing file: {}", path.to_str().unwrap());
-        let roots = serde_json::from_reader::<BufReader<File>, Vec<Root>>(reader).unwrap();
-        process_roots(&pool, roots).await.unwrap();
+        })
+        .collect()
+}
+
+fn read_records(path: &Path) -> Vec<serde_json::Value> {
+    let file = File::open(path).unwrap();
+    let reader = BufReader::new(file);
+    serde_json::from_reader::<BufReader<File>, Vec<serde_json::Value>>(reader).unwrap()
+}
+
+/// Re-derive the normalised tables from `raw_record`, optionally emptying them first.
+async fn replay(pool: &Pool<Postgres>, truncate: bool) -> sink::Result<()> {
+    create_tables(pool).await?;
+    let context = Context::from_env();
+    if context.rules.aborts() {
+        // Check every archived record before truncating or loading any
+        let (mut after, mut failed) = (0, 0);
+        loop {
+            let page = raw::page(pool, after, 10000).await?;
+            let Some(&(last, _)) = page.last() else {
+                break;
+            };
+            after = last;
+            let roots = raw::roots(page.into_iter().map(|(_, record)| record).collect());
+            failed += context.rules.check_aborts(&roots, &context.times).is_err() as usize;
+        }
+        if failed > 0 {
+            return Err("Archived records break abort rules, nothing was replayed".into());
+        }
+    }
+    if truncate {
+        // Reference countries stay, everything derived from records goes, including manual merges
+        warn!("Truncating normalised tables");
+        sqlx::query(
+            "TRUNCATE article, source, people, country_conflict, country_unmatched RESTART IDENTITY CASCADE",
+        )
+        .execute(pool)
+        .await?;
+    }
+    let sinks: Vec<Box<dyn Sink>> = vec![Box::new(PgSink::new(pool.clone()))];
+    let mut after = 0;
+    loop {
+        let page = raw::page(pool, after, 10000).await?;
+        let Some(&(last, _)) = page.last() else {
+            break;
+        };
+        warn!("Replaying {} records after {}", page.len(), after);
+        after = last;
+        process_roots(&sinks, &context, raw::roots(page.into_iter().map(|(_, record)| record).collect())).await.unwrap();
     }
     Ok(())
 }
 
-async fn process_roots(pool: &Pool<Postgres>, roots: Vec<Root>) -> Result<(), Error> {
+/// Settings read once per run and shared by every record.
+struct Context {
+    article_key: ArticleKey,
+    missing_headline: MissingHeadline,
+    normalizer: Normalizer,
+    countries: Reference,
+    times: TimeParser,
+    rules: Rules,
+}
+
+impl Context {
+    fn from_env() -> Context {
+        Context {
+            article_key: ArticleKey::from_env(),
+            missing_headline: MissingHeadline::from_env(),
+            normalizer: Normalizer::from_env(),
+            countries: Reference::bundled(),
+            times: TimeParser::from_env(),
+            rules: Rules::from_env(),
+        }
+    }
+}
+
+async fn process_roots(sinks: &[Box<dyn Sink>], context: &Context, roots: Vec<Root>) -> sink::Result<()> {
+    let roots = context.rules.apply(roots, &context.times)?;
+
     let mut futs = FuturesUnordered::new();
     let pb = ProgressBar::new(roots.len() as u64);
     pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
@@ -148,185 +486,54 @@ async fn process_roots(pool: &Pool<Postgres>, roots: Vec<Root>) -> Result<(), Er
         .progress_chars("#>-"));
 
     for x in roots {
-        let pool = pool.clone();
         let pb = pb.clone();
         let fut = async move {
-            let mut source_ids = Vec::with_capacity(x.source.len());
-            for source in x.source {
-                if source.name.is_none() {
-                    continue;
-                }
-                let country_id = match source.country {
-                    Some(_) => Some(
-                        retry(ExponentialBackoff::default(), || async {
-                            Ok(
-                                match sqlx::query(
-                                    "INSERT INTO country (name, geography, belt_and_road) \
-                                        VALUES ($1, $2, $3) ON CONFLICT (name) DO NOTHING RETURNING id",
-                                )
-                                    .bind(&source.country)
-                                    .bind(&source.geography)
-                                    .bind(&source.orob.is_some())
-                                    .fetch_one(&pool)
-                                    .await
-                                {
-                                    Ok(row) => row.get::<i32, _>("id"),
-                                    Err(e1) => sqlx::query("SELECT id FROM country WHERE name = $1")
-                                        .bind(&source.country)
-                                        .fetch_one(&pool)
-                                        .await.inspect_err(|e2| error!("1. {:?}, 2. {:?}", e1, e2))?
-                                        .get::<i32, _>("id"),
-                                }
-                            )
-                        }).await?
-                    ),
-                    None => None,
-                };
-                let id = retry(ExponentialBackoff::default(), || async {
-                    Ok(
-                        match sqlx::query(
-                            "INSERT INTO source (name, country_id, origin) \
-                            VALUES ($1, $2, $3) ON CONFLICT (name) DO NOTHING RETURNING id",
-                        )
-                            .bind(&source.name)
-                            .bind(country_id)
-                            .bind(&source.get_from())
-                            .fetch_one(&pool)
-                            .await
-                        {
-                            Ok(row) => row.get::<i32, _>("id"),
-                            Err(e1) => sqlx::query("SELECT id FROM source WHERE name = $1")
-                                .bind(&source.name)
-                                .fetch_one(&pool)
-                                .await.inspect_err(|e2| error!("1. {:?}, 2. {:?}", e1, e2))?
-                                .get::<i32, _>("id"),
-                        }
-                    )
-                }).await?;
-                source_ids.push(id);
+            if x.headline.is_none() && context.missing_headline == MissingHeadline::Skip {
+                pb.suspend(|| warn!("Skip record without headline: {:?}", x.media_id));
+                pb.inc(1);
+                return Ok(());
             }
-
-            let update_time = match x.update_time {
-                Some(time) => parse_time(time.as_str()).unwrap_or(0),
-                None => 0,
+            let Some(key) = context.article_key.derive(&x) else {
+                pb.suspend(|| warn!("Skip record without {:?} article key: {:?}", context.article_key, x.headline));
+                pb.inc(1);
+                return Ok(());
             };
-
-            let article_id = retry(ExponentialBackoff::default(), || async {
-                Ok(
-                    match sqlx::query(
-                        "INSERT INTO article (title, time) \
-                        VALUES ($1, $2) ON CONFLICT (title) DO NOTHING RETURNING id",
-                    )
-                        .bind(&x.headline)
-                        .bind(update_time)
-                        .fetch_one(&pool)
-                        .await
-                    {
-                        Ok(row) => row.get::<i32, _>("id"),
-                        Err(e1) => sqlx::query("SELECT id FROM article WHERE title = $1")
-                            .bind(&x.headline)
-                            .fetch_one(&pool)
-                            .await.inspect_err(|e2| pb.suspend(|| error!("1. {:?}, 2. {:?}", e1, e2)))?
-                            .get::<i32, _>("id"),
-                    },
-                )
-            })
-                .await?;
-
-            let people_country_id = match x.people.country {
-                Some(_) => Some(
-                    retry(ExponentialBackoff::default(), || async {
-                        Ok(
-                            match sqlx::query(
-                                "INSERT INTO country (name, geography, belt_and_road) \
-                                    VALUES ($1, $2, $3) ON CONFLICT (name) DO NOTHING RETURNING id",
-                            )
-                                .bind(&x.people.country)
-                                .bind(&x.people.geography)
-                                .bind(&x.people.orob.is_some())
-                                .fetch_one(&pool)
-                                .await
-                            {
-                                Ok(row) => row.get::<i32, _>("id"),
-                                Err(e1) => sqlx::query("SELECT id FROM country WHERE name = $1")
-                                    .bind(&x.people.country)
-                                    .fetch_one(&pool)
-                                    .await.inspect_err(|e2| error!("1. {:?}, 2. {:?}", e1, e2))?
-                                    .get::<i32, _>("id"),
-                            }
-                        )
-                    })
-                        .await?,
-                ),
-                None => None,
+            let parse_time = |field: &str, raw: Option<&str>| {
+                let raw = raw?;
+                let time = context.times.parse(raw);
+                if time.is_none() {
+                    pb.suspend(|| warn!("Unparseable {}: {:?}", field, raw));
+                }
+                time
             };
-
-            let author_id = retry(ExponentialBackoff::default(), || async {
-                Ok(
-                    match sqlx::query(
-                        "INSERT INTO people (name, country_id, origin, title, identity) \
-                        VALUES ($1, $2, $3, $4, $5) ON CONFLICT (name) DO NOTHING RETURNING id",
-                    )
-                        .bind(&x.people.name)
-                        .bind(people_country_id)
-                        .bind(&x.people.get_from())
-                        .bind(&x.people.title)
-                        .bind(&x.people.get_identity())
-                        .fetch_one(&pool)
-                        .await
-                    {
-                        Ok(row) => row.get::<i32, _>("id"),
-                        Err(e1) => sqlx::query("SELECT id FROM people WHERE name = $1")
-                            .bind(&x.people.name)
-                            .fetch_one(&pool)
-                            .await.inspect_err(|e2| pb.suspend(|| error!("1. {:?}, 2. {:?}", e1, e2)))?
-                            .get::<i32, _>("id"),
-                    }
-                )
-            }).await?;
-
-            for source_id in source_ids {
-                retry(ExponentialBackoff::default(), || async {
-                    Ok(sqlx::query(
-                        "INSERT INTO source_article (source_id, article_id) \
-                    VALUES ($1, $2) ON CONFLICT DO NOTHING",
-                    )
-                        .bind(&source_id)
-                        .bind(&article_id)
-                        .execute(&pool)
-                        .await.inspect_err(|e| pb.suspend(|| error!("1. {:?}", e)))?)
-                })
-                    .await?;
+            let updated_at = parse_time("Update_Time", x.update_time.as_deref());
+            let time = parse_time("Time", x.time.as_deref());
+            for (stage, raw) in x.pipeline_states() {
+                if Status::parse(raw) == Status::Unknown {
+                    pb.suspend(|| warn!("Unknown {} state: {:?}", stage.as_str(), raw));
+                }
+            }
+            let orobs = x.source.iter().map(|s| &s.orob).chain([&x.people.orob]);
+            for raw in orobs.flatten().filter(|raw| parse_orob(raw).is_none()) {
+                pb.suspend(|| warn!("Unknown Orob value: {:?}", raw));
+            }
+            let name = x.people.canonical_name().map(|n| context.normalizer.apply(n)).filter(|n| !n.is_empty());
+            if name.is_none() {
+                pb.suspend(|| warn!("Skip people without name in article: {:?}", x.headline));
             }
 
-            for op in x.people.opinion {
-                retry(ExponentialBackoff::default(), || async {
-                    let result = sqlx::query(
-                        "INSERT INTO opinion (author_id, text, article_id) \
-                                VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
-                    )
-                        .bind(&author_id)
-                        .bind(&op.text)
-                        .bind(&article_id)
-                        .execute(&pool)
-                        .await;
-                    Ok(
-                        match result {
-                            Err(e) => {
-                                let error = e.as_database_error().unwrap();
-                                match error.code().unwrap().as_ref() {
-                                    "54000" => Ok(()),
-                                    _ => Err(e),
-                                }
-                            }
-                            Ok(_) => Ok(()),
-                        }.inspect_err(|e| pb.suspend(|| error!("1. {:?}", e)))?,
-                    )
-                })
-                    .await?;
+            let record = Record {
+                root: &x,
+                key: &key,
+                time,
+                updated_at,
+                name: name.as_deref(),
+            };
+            for sink in sinks {
+                record.write(sink.as_ref(), context, &pb).await?;
             }
             pb.inc(1);
-            Ok::<(), Error>(())
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
         };
         futs.push(fut);
         if futs.len() >= 400 {
@@ -337,13 +544,72 @@ async fn process_roots(pool: &Pool<Postgres>, roots: Vec<Root>) -> Result<(), Er
     Ok(())
 }
 
-fn parse_time(time: &str) -> Option<i64> {
-    Some(
-        chrono::NaiveDate::parse_from_str(time, "%Y-%m-%d")
-            .ok()?
-            .and_hms_opt(0, 0, 0)?
-            .timestamp(),
-    )
+/// A record with its key, times and person name already derived.
+struct Record<'a> {
+    root: &'a Root,
+    key: &'a str,
+    time: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    name: Option<&'a str>,
+}
+
+impl Record<'_> {
+    async fn write(&self, sink: &dyn Sink, context: &Context, pb: &ProgressBar) -> sink::Result<()> {
+        let x = self.root;
+        let mut source_ids = Vec::with_capacity(x.source.len());
+        for source in &x.source {
+            let Some(source_name) = source.name.as_deref().map(|n| context.normalizer.apply(n)).filter(|n| !n.is_empty()) else {
+                continue;
+            };
+            let country_id = self.country_id(sink, source.country.as_deref(), &source.country_attributes(), context, pb).await?;
+            let id = sink.resolve_source(&source_name, country_id, self.updated_at).await?;
+            sink.add_source_details(id, source).await?;
+            source_ids.push(id);
+        }
+
+        let article_id = sink.upsert_article(self.key, x, self.time, self.updated_at).await?;
+        for (stage, raw) in x.pipeline_states() {
+            sink.set_pipeline_state(article_id, stage, Status::parse(raw), raw).await?;
+        }
+        for source_id in source_ids {
+            sink.link_source(source_id, article_id).await?;
+        }
+
+        let Some(name) = self.name else {
+            return Ok(());
+        };
+        let country_id = self.country_id(sink, x.people.country.as_deref(), &x.people.country_attributes(), context, pb).await?;
+        let author_id = sink.resolve_person(name, country_id, &x.people, self.updated_at).await?;
+        sink.add_person_details(author_id, &x.people).await?;
+        for op in &x.people.opinion {
+            sink.add_opinion(author_id, article_id, op).await?;
+        }
+        Ok(())
+    }
+
+    /// Resolve `raw` through the reference data and upsert the country with the record's attributes.
+    async fn country_id(
+        &self,
+        sink: &dyn Sink,
+        raw: Option<&str>,
+        attributes: &Attributes<'_>,
+        context: &Context,
+        pb: &ProgressBar,
+    ) -> sink::Result<Option<i32>> {
+        let Some(raw) = raw.map(str::trim).filter(|c| !c.is_empty()) else {
+            return Ok(None);
+        };
+        let Some(country) = context.countries.resolve(raw) else {
+            if sink.record_unmatched_country(raw).await? {
+                pb.suspend(|| warn!("Unknown country: {:?}", raw));
+            }
+            return Ok(None);
+        };
+        for (attribute, stored, incoming) in sink.record_country_conflicts(country.name, attributes).await? {
+            pb.suspend(|| warn!("Conflicting {} for {}: {:?} vs {:?}", attribute, country.name, stored, incoming));
+        }
+        Ok(Some(sink.resolve_country(country.name, attributes, self.updated_at).await?))
+    }
 }
 
 // fn traverse_and_move(path: &Path, target: &Path) {
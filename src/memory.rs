@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::country::{Attributes, Reference};
+use crate::person::{self, Candidate};
+use crate::schema::{Opinion, People, Root};
+use crate::sink::{self, Sink};
+use crate::table::{Table, Value};
+use crate::upsert::Policy;
+
+/// A table with the natural key of each row, which keeps rows by id.
+struct Keyed<K> {
+    table: Table,
+    ids: HashMap<K, i32>,
+    policy: Policy,
+    columns: Vec<usize>,
+    updated_at: usize,
+}
+
+impl<K: std::hash::Hash + Eq> Keyed<K> {
+    fn new(name: &'static str, policy: Policy, columns: &[&str]) -> Keyed<K> {
+        let table = Table::new(name);
+        Keyed {
+            columns: columns.iter().map(|c| table.column(c)).collect(),
+            updated_at: table.column("updated_at"),
+            table,
+            ids: HashMap::new(),
+            policy,
+        }
+    }
+
+    /// Insert `row`, whose first column is a placeholder for the id, or merge
+    /// it into the row with the same key.
+    fn upsert(&mut self, key: K, mut row: Vec<Value>) -> i32 {
+        match self.ids.get(&key) {
+            Some(&id) => {
+                let stored = &mut self.table.rows[id as usize - 1];
+                self.policy.merge(stored, &row, &self.columns, self.updated_at);
+                id
+            }
+            None => {
+                let id = self.table.rows.len() as i32 + 1;
+                row[0] = Value::Int(id);
+                self.table.rows.push(row);
+                self.ids.insert(key, id);
+                id
+            }
+        }
+    }
+}
+
+struct State {
+    country: Keyed<String>,
+    source: Keyed<String>,
+    people: Keyed<(String, Option<i32>)>,
+    /// Ids of the people with each name, in any country.
+    people_names: HashMap<String, Vec<i32>>,
+    article: Keyed<String>,
+    source_article: HashSet<(i32, i32)>,
+    opinion: Table,
+    opinion_texts: HashSet<String>,
+}
+
+/// Keeps records in memory with ids assigned in-process, for export to files.
+/// Clones share the same tables.
+#[derive(Clone)]
+pub struct MemorySink {
+    state: Arc<Mutex<State>>,
+}
+
+impl MemorySink {
+    /// An empty sink with the reference countries in place and the upsert
+    /// policies read from the environment.
+    pub fn new() -> MemorySink {
+        let mut country = Keyed::new(
+            "country",
+            Policy::from_env("country", Policy::MergeNonNull),
+            &["geography", "belt_and_road", "bri_since", "orob_region", "geopolitics"],
+        );
+        for c in Reference::bundled().countries() {
+            let mut row = vec![Value::Null; country.table.columns.len()];
+            row[1] = Value::Text(c.name.to_string());
+            row[2] = Value::Text(c.alpha2.to_string());
+            row[3] = Value::Text(c.alpha3.to_string());
+            row[4] = Value::Text(c.region.to_string());
+            country.upsert(c.name.to_string(), row);
+        }
+        MemorySink {
+            state: Arc::new(Mutex::new(State {
+                country,
+                source: Keyed::new("source", Policy::from_env("source", Policy::FirstWins), &["country_id"]),
+                people: Keyed::new("people", Policy::from_env("people", Policy::FirstWins), &["origin", "title"]),
+                article: Keyed::new(
+                    "article",
+                    Policy::from_env("article", Policy::FirstWins),
+                    &["title", "time", "media_id", "original_site", "abstract", "body"],
+                ),
+                source_article: HashSet::new(),
+                opinion: Table::new("opinion"),
+                people_names: HashMap::new(),
+                opinion_texts: HashSet::new(),
+            })),
+        }
+    }
+
+    /// The tables in dependency order, with `source_article` sorted.
+    /// Panics unless this is the last clone.
+    pub fn tables(self) -> Vec<Table> {
+        let state = Arc::into_inner(self.state).unwrap().into_inner().unwrap();
+        let mut links: Vec<_> = state.source_article.into_iter().collect();
+        links.sort_unstable();
+        let mut source_article = Table::new("source_article");
+        source_article.rows = links
+            .into_iter()
+            .map(|(s, a)| vec![Value::Int(s), Value::Int(a)])
+            .collect();
+        vec![
+            state.country.table,
+            state.source.table,
+            state.people.table,
+            state.article.table,
+            source_article,
+            state.opinion,
+        ]
+    }
+}
+
+#[async_trait]
+impl Sink for MemorySink {
+    async fn resolve_country(
+        &self,
+        name: &str,
+        attributes: &Attributes<'_>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        let row = vec![
+            Value::Null,
+            Some(name).into(),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            attributes.geography.into(),
+            attributes.orob.map(|o| o.member).into(),
+            attributes.orob.and_then(|o| o.since).into(),
+            attributes.orob_region.into(),
+            attributes.geopolitics.into(),
+            updated_at.into(),
+        ];
+        Ok(self.state.lock().unwrap().country.upsert(name.to_string(), row))
+    }
+
+    async fn resolve_source(&self, name: &str, country_id: Option<i32>, updated_at: Option<DateTime<Utc>>) -> sink::Result<i32> {
+        let row = vec![Value::Null, Some(name).into(), country_id.into(), updated_at.into()];
+        Ok(self.state.lock().unwrap().source.upsert(name.to_string(), row))
+    }
+
+    async fn upsert_article(
+        &self,
+        key: &str,
+        root: &Root,
+        time: Option<DateTime<Utc>>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        let row = vec![
+            Value::Null,
+            Some(key).into(),
+            root.headline.as_deref().into(),
+            time.into(),
+            root.media_id.as_deref().into(),
+            root.original_site.as_deref().into(),
+            root.abstract_field.as_deref().into(),
+            root.body.as_deref().into(),
+            updated_at.into(),
+        ];
+        Ok(self.state.lock().unwrap().article.upsert(key.to_string(), row))
+    }
+
+    async fn link_source(&self, source_id: i32, article_id: i32) -> sink::Result<()> {
+        self.state.lock().unwrap().source_article.insert((source_id, article_id));
+        Ok(())
+    }
+
+    async fn resolve_person(
+        &self,
+        name: &str,
+        country_id: Option<i32>,
+        people: &People,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        let mut state = self.state.lock().unwrap();
+        let State { people: people_table, people_names, .. } = &mut *state;
+        let (country_column, title_column) = (2, people_table.table.column("title"));
+        let candidates: Vec<Candidate> = people_names
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|&id| {
+                let row = &people_table.table.rows[id as usize - 1];
+                let int = |v: &Value| match v {
+                    Value::Int(i) => Some(*i),
+                    _ => None,
+                };
+                let text = |v: &Value| match v {
+                    Value::Text(s) => Some(s.clone()),
+                    _ => None,
+                };
+                (int(&row[0]).unwrap(), name.to_string(), int(&row[country_column]), text(&row[title_column]))
+            })
+            .collect();
+        let resolution = person::choose(&candidates, name, country_id, people.title.as_deref());
+        if let Some(id) = resolution.adopt {
+            people_table.table.rows[id as usize - 1][country_column] = resolution.country_id.into();
+            people_table.ids.remove(&(resolution.name.clone(), None));
+            people_table.ids.insert((resolution.name.clone(), resolution.country_id), id);
+        }
+        let row = vec![
+            Value::Null,
+            Some(resolution.name.as_str()).into(),
+            resolution.country_id.into(),
+            people.title.as_deref().into(),
+            people.get_from().as_deref().into(),
+            updated_at.into(),
+        ];
+        let known = people_table.table.rows.len();
+        let id = people_table.upsert((resolution.name.clone(), resolution.country_id), row);
+        if people_table.table.rows.len() > known {
+            people_names.entry(resolution.name).or_default().push(id);
+        }
+        Ok(id)
+    }
+
+    async fn add_opinion(&self, author_id: i32, article_id: i32, opinion: &Opinion) -> sink::Result<()> {
+        let Some(text) = &opinion.text else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap();
+        if state.opinion_texts.insert(text.clone()) {
+            let id = state.opinion.rows.len() as i32 + 1;
+            state.opinion.rows.push(vec![
+                Value::Int(id),
+                Value::Int(author_id),
+                Value::Text(text.clone()),
+                Value::Int(article_id),
+                opinion.score.map_or(Value::Null, Value::Float),
+                opinion.start.map_or(Value::Null, Value::BigInt),
+                opinion.end.map_or(Value::Null, Value::BigInt),
+            ]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,553 @@
+use async_trait::async_trait;
+use backoff::future::retry;
+use backoff::ExponentialBackoff;
+use chrono::{DateTime, Utc};
+use sqlx::mysql::MySqlPoolOptions;
+use sqlx::{Error, MySql, Pool, Row};
+
+use crate::country::{Attributes, Reference};
+use crate::identity::Identity;
+use crate::person::{self, Candidate};
+use crate::pipeline::{Stage, Status};
+use crate::schema::{Opinion, People, Root, Source};
+use crate::sink::{self, Sink};
+use crate::upsert::Policy;
+
+/// The Postgres tables written by the migration, in MySQL syntax.
+///
+/// Tables use a binary collation so names compare exactly as in Postgres.
+/// Names and other texts are unique through a stored SHA-256 column, and
+/// nullable parts of a key through stored `COALESCE` columns, since MySQL
+/// indexes neither directly. Lookups by name go through the hash. The raw archive, search, audit and conflict tables are Postgres only.
+const TABLES: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS country (
+        id INT AUTO_INCREMENT PRIMARY KEY,
+        name TEXT NOT NULL,
+        name_hash CHAR(64) AS (SHA2(name, 256)) STORED UNIQUE,
+        iso_alpha2 CHAR(2) UNIQUE,
+        iso_alpha3 CHAR(3) UNIQUE,
+        region TEXT,
+        geography TEXT,
+        belt_and_road BOOLEAN,
+        bri_since DATE,
+        orob_region TEXT,
+        geopolitics TEXT,
+        updated_at DATETIME(6)
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS country_unmatched (
+        name TEXT NOT NULL,
+        name_hash CHAR(64) AS (SHA2(name, 256)) STORED UNIQUE,
+        occurrences INT NOT NULL DEFAULT 1,
+        first_seen DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6)
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS people (
+        id INT AUTO_INCREMENT PRIMARY KEY,
+        name TEXT NOT NULL,
+        name_hash CHAR(64) AS (SHA2(name, 256)) STORED,
+        country_id INT,
+        country_key INT AS (COALESCE(country_id, 0)) STORED,
+        title TEXT,
+        origin TEXT,
+        updated_at DATETIME(6),
+        UNIQUE KEY people_name_country (name_hash, country_key),
+        -- A stored generated column cannot depend on a column with a cascading foreign key
+        CONSTRAINT fk_people_country
+            FOREIGN KEY (country_id)
+            REFERENCES country(id)
+            ON DELETE RESTRICT
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS people_alias (
+        people_id INT NOT NULL,
+        alias TEXT NOT NULL,
+        alias_hash CHAR(64) AS (SHA2(alias, 256)) STORED,
+        UNIQUE KEY (people_id, alias_hash),
+        KEY (alias_hash),
+        CONSTRAINT fk_people_alias_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS people_account (
+        people_id INT NOT NULL,
+        platform VARCHAR(32) NOT NULL,
+        account TEXT NOT NULL,
+        account_hash CHAR(64) AS (SHA2(account, 256)) STORED,
+        url TEXT,
+        screenshot TEXT,
+        UNIQUE KEY (people_id, platform, account_hash),
+        CONSTRAINT fk_people_account_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS legislator_term (
+        people_id INT NOT NULL,
+        chamber TEXT,
+        state TEXT,
+        district TEXT,
+        party TEXT,
+        period TEXT,
+        start_year INT,
+        end_year INT,
+        term_hash CHAR(64) AS (SHA2(CONCAT_WS(CHAR(31),
+            COALESCE(chamber, ''), COALESCE(state, ''), COALESCE(district, ''),
+            COALESCE(party, ''), COALESCE(period, '')), 256)) STORED,
+        UNIQUE KEY (people_id, term_hash),
+        CONSTRAINT fk_legislator_term_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS source (
+        id INT AUTO_INCREMENT PRIMARY KEY,
+        name TEXT NOT NULL,
+        name_hash CHAR(64) AS (SHA2(name, 256)) STORED UNIQUE,
+        country_id INT,
+        updated_at DATETIME(6),
+        CONSTRAINT fk_source_country
+            FOREIGN KEY (country_id)
+            REFERENCES country(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS source_affiliation (
+        source_id INT NOT NULL,
+        kind VARCHAR(64) NOT NULL,
+        value TEXT NOT NULL,
+        value_hash CHAR(64) AS (SHA2(value, 256)) STORED,
+        UNIQUE KEY (source_id, kind, value_hash),
+        KEY (kind, value_hash),
+        CONSTRAINT fk_source_affiliation_source
+            FOREIGN KEY (source_id)
+            REFERENCES source(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS source_alias (
+        source_id INT NOT NULL,
+        alias TEXT NOT NULL,
+        alias_hash CHAR(64) AS (SHA2(alias, 256)) STORED,
+        UNIQUE KEY (source_id, alias_hash),
+        KEY (alias_hash),
+        CONSTRAINT fk_source_alias_source
+            FOREIGN KEY (source_id)
+            REFERENCES source(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS article (
+        id INT AUTO_INCREMENT PRIMARY KEY,
+        `key` TEXT NOT NULL,
+        key_hash CHAR(64) AS (SHA2(`key`, 256)) STORED UNIQUE,
+        title TEXT,
+        time DATETIME(6),
+        media_id TEXT,
+        original_site TEXT,
+        abstract MEDIUMTEXT,
+        body LONGTEXT,
+        updated_at DATETIME(6)
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS source_article (
+        source_id INT NOT NULL,
+        article_id INT NOT NULL,
+        UNIQUE KEY (source_id, article_id),
+        CONSTRAINT fk_source_article_source
+            FOREIGN KEY (source_id)
+            REFERENCES source(id)
+            ON DELETE CASCADE,
+        CONSTRAINT fk_source_article_article
+            FOREIGN KEY (article_id)
+            REFERENCES article(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS opinion (
+        id INT AUTO_INCREMENT PRIMARY KEY,
+        author_id INT NOT NULL,
+        text MEDIUMTEXT NOT NULL,
+        text_hash CHAR(64) AS (SHA2(text, 256)) STORED UNIQUE,
+        article_id INT NOT NULL,
+        CONSTRAINT fk_opinion_author
+            FOREIGN KEY (author_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE,
+        CONSTRAINT fk_opinion_article
+            FOREIGN KEY (article_id)
+            REFERENCES article(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS article_pipeline_state (
+        article_id INT NOT NULL,
+        stage VARCHAR(64) NOT NULL,
+        status VARCHAR(16) NOT NULL CHECK (status IN ('pending', 'done', 'failed', 'unknown')),
+        raw TEXT,
+        UNIQUE KEY (article_id, stage),
+        CONSTRAINT fk_article_pipeline_state_article
+            FOREIGN KEY (article_id)
+            REFERENCES article(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS identity (
+        id INT AUTO_INCREMENT PRIMARY KEY,
+        name VARCHAR(64) NOT NULL UNIQUE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+    "CREATE TABLE IF NOT EXISTS people_identity (
+        people_id INT NOT NULL,
+        identity_id INT NOT NULL,
+        value TEXT NOT NULL,
+        value_hash CHAR(64) AS (SHA2(value, 256)) STORED,
+        UNIQUE KEY (people_id, identity_id, value_hash),
+        CONSTRAINT fk_people_identity_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE,
+        CONSTRAINT fk_people_identity_identity
+            FOREIGN KEY (identity_id)
+            REFERENCES identity(id)
+            ON DELETE CASCADE
+    ) DEFAULT CHARSET = utf8mb4 COLLATE = utf8mb4_bin",
+];
+
+pub async fn create_tables(pool: &Pool<MySql>) -> Result<(), Error> {
+    for table in TABLES {
+        sqlx::query(table).execute(pool).await?;
+    }
+    let countries = Reference::bundled();
+    let countries = countries.countries();
+    let sql = format!(
+        "INSERT INTO country (name, iso_alpha2, iso_alpha3, region) VALUES {} \
+        ON DUPLICATE KEY UPDATE iso_alpha2 = VALUES(iso_alpha2), \
+        iso_alpha3 = VALUES(iso_alpha3), region = VALUES(region)",
+        vec!["(?, ?, ?, ?)"; countries.len()].join(", "),
+    );
+    let mut seed = sqlx::query(&sql);
+    for c in countries {
+        seed = seed.bind(c.name).bind(c.alpha2).bind(c.alpha3).bind(c.region);
+    }
+    seed.execute(pool).await?;
+    let sql = format!(
+        "INSERT INTO identity (name) VALUES {} ON DUPLICATE KEY UPDATE name = name",
+        vec!["(?)"; Identity::ALL.len()].join(", "),
+    );
+    let mut seed = sqlx::query(&sql);
+    for identity in Identity::ALL {
+        seed = seed.bind(identity.as_str());
+    }
+    seed.execute(pool).await?;
+    Ok(())
+}
+
+/// Writes records into MySQL or MariaDB, read from `MYSQL_URL`.
+pub struct MySqlSink {
+    pool: Pool<MySql>,
+    country_sql: String,
+    source_sql: String,
+    article_sql: String,
+    people_sql: String,
+}
+
+impl MySqlSink {
+    pub async fn connect() -> Result<MySqlSink, Error> {
+        let url = std::env::var("MYSQL_URL").expect("MYSQL_URL must be set.");
+        let pool = MySqlPoolOptions::new().max_connections(100).connect(&url).await?;
+        create_tables(&pool).await?;
+        Ok(MySqlSink {
+            pool,
+            country_sql: format!(
+                "INSERT INTO country (name, geography, belt_and_road, bri_since, orob_region, geopolitics, updated_at) \
+                VALUES (?, ?, ?, ?, ?, ?, ?) {}",
+                Policy::from_env("country", Policy::MergeNonNull).on_duplicate_key(&[
+                    "geography",
+                    "belt_and_road",
+                    "bri_since",
+                    "orob_region",
+                    "geopolitics",
+                ]),
+            ),
+            source_sql: format!(
+                "INSERT INTO source (name, country_id, updated_at) VALUES (?, ?, ?) {}",
+                Policy::from_env("source", Policy::FirstWins).on_duplicate_key(&["country_id"]),
+            ),
+            article_sql: format!(
+                "INSERT INTO article (`key`, title, time, media_id, original_site, abstract, body, updated_at) \
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?) {}",
+                Policy::from_env("article", Policy::FirstWins).on_duplicate_key(&[
+                    "title",
+                    "time",
+                    "media_id",
+                    "original_site",
+                    "abstract",
+                    "body",
+                ]),
+            ),
+            people_sql: format!(
+                "INSERT INTO people (name, country_id, origin, title, updated_at) VALUES (?, ?, ?, ?, ?) {}",
+                Policy::from_env("people", Policy::FirstWins).on_duplicate_key(&["origin", "title"]),
+            ),
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for MySqlSink {
+    async fn record_unmatched_country(&self, raw: &str) -> sink::Result<bool> {
+        // MySQL reports one affected row for an insert and two for an update
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "INSERT INTO country_unmatched (name) VALUES (?) \
+                ON DUPLICATE KEY UPDATE occurrences = occurrences + 1",
+            )
+            .bind(raw)
+            .execute(&self.pool)
+            .await?)
+        })
+        .await?
+        .rows_affected()
+            == 1)
+    }
+
+    async fn resolve_country(
+        &self,
+        name: &str,
+        attributes: &Attributes<'_>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(&self.country_sql)
+                .bind(name)
+                .bind(attributes.geography)
+                .bind(attributes.orob.map(|o| o.member))
+                .bind(attributes.orob.and_then(|o| o.since))
+                .bind(attributes.orob_region)
+                .bind(attributes.geopolitics)
+                .bind(updated_at)
+                .execute(&self.pool)
+                .await?)
+        })
+        .await?
+        .last_insert_id() as i32)
+    }
+
+    async fn resolve_source(&self, name: &str, country_id: Option<i32>, updated_at: Option<DateTime<Utc>>) -> sink::Result<i32> {
+        let name = retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query("SELECT s.name FROM source_alias a JOIN source s ON s.id = a.source_id WHERE a.alias_hash = SHA2(?, 256)")
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await?)
+        })
+        .await?
+        .map_or(name.to_string(), |row| row.get::<String, _>("name"));
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(&self.source_sql)
+                .bind(&name)
+                .bind(country_id)
+                .bind(updated_at)
+                .execute(&self.pool)
+                .await?)
+        })
+        .await?
+        .last_insert_id() as i32)
+    }
+
+    async fn add_source_details(&self, source_id: i32, source: &Source) -> sink::Result<()> {
+        for (affiliation, value) in source.affiliations() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO source_affiliation (source_id, kind, value) VALUES (?, ?, ?) \
+                    ON DUPLICATE KEY UPDATE source_id = source_id",
+                )
+                .bind(source_id)
+                .bind(affiliation.as_str())
+                .bind(value)
+                .execute(&self.pool)
+                .await?)
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn upsert_article(
+        &self,
+        key: &str,
+        root: &Root,
+        time: Option<DateTime<Utc>>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(&self.article_sql)
+                .bind(key)
+                .bind(&root.headline)
+                .bind(time)
+                .bind(&root.media_id)
+                .bind(&root.original_site)
+                .bind(&root.abstract_field)
+                .bind(&root.body)
+                .bind(updated_at)
+                .execute(&self.pool)
+                .await?)
+        })
+        .await?
+        .last_insert_id() as i32)
+    }
+
+    async fn set_pipeline_state(&self, article_id: i32, stage: Stage, status: Status, raw: &str) -> sink::Result<()> {
+        retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "INSERT INTO article_pipeline_state (article_id, stage, status, raw) VALUES (?, ?, ?, ?) \
+                ON DUPLICATE KEY UPDATE status = VALUES(status), raw = VALUES(raw)",
+            )
+            .bind(article_id)
+            .bind(stage.as_str())
+            .bind(status.as_str())
+            .bind(raw)
+            .execute(&self.pool)
+            .await?)
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn link_source(&self, source_id: i32, article_id: i32) -> sink::Result<()> {
+        retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "INSERT INTO source_article (source_id, article_id) VALUES (?, ?) \
+                ON DUPLICATE KEY UPDATE source_id = source_id",
+            )
+            .bind(source_id)
+            .bind(article_id)
+            .execute(&self.pool)
+            .await?)
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn resolve_person(
+        &self,
+        name: &str,
+        country_id: Option<i32>,
+        people: &People,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        let candidates: Vec<Candidate> = retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "SELECT id, name, country_id, title FROM people WHERE name_hash = SHA2(?, 256) \
+                UNION SELECT p.id, p.name, p.country_id, p.title FROM people_alias a \
+                JOIN people p ON p.id = a.people_id WHERE a.alias_hash = SHA2(?, 256)",
+            )
+            .bind(name)
+            .bind(name)
+            .fetch_all(&self.pool)
+            .await?)
+        })
+        .await?
+        .iter()
+        .map(|row| (row.get("id"), row.get("name"), row.get("country_id"), row.get("title")))
+        .collect();
+        let resolution = person::choose(&candidates, name, country_id, people.title.as_deref());
+        if let Some(id) = resolution.adopt {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query("UPDATE people SET country_id = ? WHERE id = ?")
+                    .bind(resolution.country_id)
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?)
+            })
+            .await?;
+        }
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(&self.people_sql)
+                .bind(&resolution.name)
+                .bind(resolution.country_id)
+                .bind(people.get_from())
+                .bind(&people.title)
+                .bind(updated_at)
+                .execute(&self.pool)
+                .await?)
+        })
+        .await?
+        .last_insert_id() as i32)
+    }
+
+    async fn add_person_details(&self, people_id: i32, people: &People) -> sink::Result<()> {
+        for alias in [people.name.as_deref(), people.name_clean.as_deref()].into_iter().flatten() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO people_alias (people_id, alias) VALUES (?, ?) \
+                    ON DUPLICATE KEY UPDATE people_id = people_id",
+                )
+                .bind(people_id)
+                .bind(alias)
+                .execute(&self.pool)
+                .await?)
+            })
+            .await?;
+        }
+        for (identity, value) in people.identities() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO people_identity (people_id, identity_id, value) \
+                    SELECT ?, id, ? FROM identity WHERE name = ? \
+                    ON DUPLICATE KEY UPDATE people_id = people_identity.people_id",
+                )
+                .bind(people_id)
+                .bind(value)
+                .bind(identity.as_str())
+                .execute(&self.pool)
+                .await?)
+            })
+            .await?;
+        }
+        for account in people.accounts() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO people_account (people_id, platform, account, url, screenshot) \
+                    VALUES (?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE \
+                    url = COALESCE(url, VALUES(url)), screenshot = COALESCE(screenshot, VALUES(screenshot))",
+                )
+                .bind(people_id)
+                .bind(account.platform.as_str())
+                .bind(&account.account)
+                .bind(account.url)
+                .bind(account.screenshot)
+                .execute(&self.pool)
+                .await?)
+            })
+            .await?;
+        }
+        if let Some(term) = people.legislator_term() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO legislator_term \
+                    (people_id, chamber, state, district, party, period, start_year, end_year) \
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE people_id = people_id",
+                )
+                .bind(people_id)
+                .bind(term.chamber)
+                .bind(term.state)
+                .bind(term.district)
+                .bind(term.party)
+                .bind(term.period)
+                .bind(term.start_year)
+                .bind(term.end_year)
+                .execute(&self.pool)
+                .await?)
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn add_opinion(&self, author_id: i32, article_id: i32, opinion: &Opinion) -> sink::Result<()> {
+        retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "INSERT INTO opinion (author_id, text, article_id) VALUES (?, ?, ?) \
+                ON DUPLICATE KEY UPDATE id = id",
+            )
+            .bind(author_id)
+            .bind(&opinion.text)
+            .bind(article_id)
+            .execute(&self.pool)
+            .await?)
+        })
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use unicode_normalization::UnicodeNormalization;
+
+const HONORIFICS: &[&str] = &[
+    "mr", "mrs", "ms", "miss", "mx", "dr", "prof", "professor", "sir", "dame", "lord", "lady", "hon",
+    "honorable", "honourable", "rev", "reverend", "sen", "senator", "rep", "representative", "gov",
+    "governor", "pres", "president", "gen", "general", "col", "colonel", "capt", "captain", "lt",
+    "h.e.", "his excellency", "her excellency", "the honorable", "the honourable",
+];
+
+/// Normalisation applied to entity names before they are looked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Normalizer {
+    pub nfkc: bool,
+    pub casefold: bool,
+    pub whitespace: bool,
+    pub punctuation: bool,
+    pub honorifics: bool,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Normalizer {
+            nfkc: true,
+            casefold: false,
+            whitespace: true,
+            punctuation: false,
+            honorifics: false,
+        }
+    }
+}
+
+impl Normalizer {
+    /// Every step, for comparing names rather than storing them.
+    pub fn all() -> Normalizer {
+        Normalizer {
+            nfkc: true,
+            casefold: true,
+            whitespace: true,
+            punctuation: true,
+            honorifics: true,
+        }
+    }
+
+    pub fn none() -> Normalizer {
+        Normalizer {
+            nfkc: false,
+            casefold: false,
+            whitespace: false,
+            punctuation: false,
+            honorifics: false,
+        }
+    }
+
+    /// Read `NORMALIZE` as a comma separated list of steps, defaulting to `nfkc,whitespace`.
+    pub fn from_env() -> Normalizer {
+        let Ok(steps) = std::env::var("NORMALIZE") else {
+            return Normalizer::default();
+        };
+        let mut normalizer = Normalizer::none();
+        for step in steps.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match step {
+                "nfkc" => normalizer.nfkc = true,
+                "casefold" => normalizer.casefold = true,
+                "whitespace" => normalizer.whitespace = true,
+                "punctuation" => normalizer.punctuation = true,
+                "honorifics" => normalizer.honorifics = true,
+                "all" => normalizer = Normalizer::all(),
+                "none" => normalizer = Normalizer::none(),
+                _ => panic!("Invalid NORMALIZE step: {}", step),
+            }
+        }
+        normalizer
+    }
+
+    pub fn apply(&self, name: &str) -> String {
+        let mut name = if self.nfkc {
+            name.nfkc().collect::<String>()
+        } else {
+            name.to_string()
+        };
+        if self.casefold {
+            name = name.to_lowercase();
+        }
+        if self.honorifics {
+            name = strip_honorifics(&name).to_string();
+        }
+        if self.punctuation {
+            name.retain(|c| c.is_alphanumeric() || c.is_whitespace());
+        }
+        if self.whitespace {
+            name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        name
+    }
+}
+
+/// Drop leading titles such as `Dr.` or `The Honorable`, keeping at least one word.
+fn strip_honorifics(mut name: &str) -> &str {
+    'outer: loop {
+        let trimmed = name.trim_start();
+        for honorific in HONORIFICS {
+            let Some(head) = trimmed.get(..honorific.len()) else {
+                continue;
+            };
+            if !head.eq_ignore_ascii_case(honorific) {
+                continue;
+            }
+            let rest = trimmed[honorific.len()..].trim_start_matches('.');
+            if rest.starts_with(char::is_whitespace) && !rest.trim().is_empty() {
+                name = rest;
+                continue 'outer;
+            }
+        }
+        return trimmed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_steps_compare_names_loosely() {
+        let all = Normalizer::all();
+        assert_eq!(all.apply("  Dr.  JOHN   Smith "), "john smith");
+        assert_eq!(all.apply("The Honorable Dr. Jane O'Neil"), "jane oneil");
+        assert_eq!(all.apply("ＪＯＥ　ＢＩＤＥＮ"), "joe biden");
+        assert_eq!(all.apply("Senator Lee"), all.apply("sen. lee"));
+    }
+
+    #[test]
+    fn honorifics_leave_at_least_one_word() {
+        assert_eq!(strip_honorifics("Dr."), "Dr.");
+        assert_eq!(strip_honorifics("General"), "General");
+        assert_eq!(strip_honorifics("Dr General"), "General");
+        // Only whole words are titles
+        assert_eq!(strip_honorifics("Drew Carey"), "Drew Carey");
+        assert_eq!(strip_honorifics("Senatorial Smith"), "Senatorial Smith");
+    }
+
+    #[test]
+    fn default_only_folds_compatibility_forms_and_spaces() {
+        let default = Normalizer::default();
+        assert_eq!(default.apply(" Ｄｒ.  John\tSmith "), "Dr. John Smith");
+        assert_eq!(Normalizer::none().apply(" Dr. John "), " Dr. John ");
+    }
+}
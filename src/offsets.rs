@@ -0,0 +1,304 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde_json::Value;
+
+/// What the `start` and `end` of an opinion count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convention {
+    /// Unicode scalar values, the canonical form.
+    Char,
+    /// UTF-16 code units, as JavaScript and Java count.
+    Utf16,
+    /// UTF-8 bytes.
+    Byte,
+}
+
+impl Convention {
+    /// In order of preference when several conventions fit, as with ASCII text.
+    pub const ALL: [Convention; 3] = [Convention::Char, Convention::Utf16, Convention::Byte];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Convention::Char => "char",
+            Convention::Utf16 => "utf16",
+            Convention::Byte => "byte",
+        }
+    }
+
+    /// The byte index of `offset` in `body`, if it falls on a character boundary.
+    fn byte_index(&self, body: &str, offset: usize) -> Option<usize> {
+        match self {
+            Convention::Char => body.char_indices().map(|(i, _)| i).chain([body.len()]).nth(offset),
+            Convention::Utf16 => {
+                let mut units = 0;
+                for (i, c) in body.char_indices().chain([(body.len(), '\0')]) {
+                    if units == offset {
+                        return Some(i);
+                    }
+                    if units > offset {
+                        return None;
+                    }
+                    units += c.len_utf16();
+                }
+                None
+            }
+            Convention::Byte => Some(offset).filter(|&i| body.is_char_boundary(i)),
+        }
+    }
+
+    /// Whether `body[start..end]` is `text` when counting in this convention.
+    pub fn matches(&self, body: &str, start: usize, end: usize, text: &str) -> bool {
+        match (self.byte_index(body, start), self.byte_index(body, end)) {
+            (Some(start), Some(end)) if start <= end => &body[start..end] == text,
+            _ => false,
+        }
+    }
+
+    /// Convert `offset` into a char offset.
+    pub fn to_char(self, body: &str, offset: usize) -> Option<usize> {
+        self.byte_index(body, offset).map(|i| body[..i].chars().count())
+    }
+}
+
+/// An opinion of a record, by its position in the file.
+pub struct Located<'a> {
+    pub record: usize,
+    pub opinion: usize,
+    pub body: &'a str,
+    pub start: usize,
+    pub end: usize,
+    pub text: &'a str,
+}
+
+/// The opinions of `records` that have a body, text and offsets to check.
+pub fn opinions(records: &[Value]) -> Vec<Located<'_>> {
+    let mut located = vec![];
+    for (r, record) in records.iter().enumerate() {
+        let Some(body) = record["Body"].as_str() else {
+            continue;
+        };
+        let Some(opinions) = record["People"]["Opinion"].as_array() else {
+            continue;
+        };
+        for (o, opinion) in opinions.iter().enumerate() {
+            let (Some(start), Some(end), Some(text)) = (
+                opinion["start"].as_u64(),
+                opinion["end"].as_u64(),
+                opinion["text"].as_str(),
+            ) else {
+                continue;
+            };
+            located.push(Located {
+                record: r,
+                opinion: o,
+                body,
+                start: start as usize,
+                end: end as usize,
+                text,
+            });
+        }
+    }
+    located
+}
+
+/// The convention most opinions match, preferring char offsets on a tie,
+/// with the number it matches. `None` when no opinion matches any convention.
+pub fn detect(opinions: &[Located]) -> Option<(Convention, usize)> {
+    let mut best: Option<(Convention, usize)> = None;
+    for convention in Convention::ALL {
+        let matched = opinions
+            .iter()
+            .filter(|o| convention.matches(o.body, o.start, o.end, o.text))
+            .count();
+        if matched > 0 && best.is_none_or(|(_, n)| matched > n) {
+            best = Some((convention, matched));
+        }
+    }
+    best
+}
+
+/// How an opinion's offsets were made canonical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repair {
+    /// The offsets match in the file's convention.
+    Matched,
+    /// The offsets only match in another convention.
+    Convention(Convention),
+    /// The offsets match nothing but the text occurs in the body; the
+    /// occurrence nearest the stated start is taken.
+    Relocated,
+    /// The text does not occur in the body.
+    Unlocatable,
+}
+
+impl Repair {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Repair::Matched => "matched",
+            Repair::Convention(Convention::Char) => "char offsets",
+            Repair::Convention(Convention::Utf16) => "utf16 offsets",
+            Repair::Convention(Convention::Byte) => "byte offsets",
+            Repair::Relocated => "relocated",
+            Repair::Unlocatable => "unlocatable",
+        }
+    }
+}
+
+/// The char offsets of `opinion`, trying `convention` first, then the other
+/// conventions, then a search of the body. Unlocatable opinions keep theirs.
+pub fn repair(opinion: &Located, convention: Convention) -> (Repair, usize, usize) {
+    let Located { body, start, end, text, .. } = *opinion;
+    let order = [convention].into_iter().chain(Convention::ALL.into_iter().filter(|c| *c != convention));
+    for c in order {
+        if c.matches(body, start, end, text) {
+            let repair = if c == convention { Repair::Matched } else { Repair::Convention(c) };
+            return (repair, c.to_char(body, start).unwrap(), c.to_char(body, end).unwrap());
+        }
+    }
+    if text.is_empty() {
+        return (Repair::Unlocatable, start, end);
+    }
+    let length = text.chars().count();
+    body.match_indices(text)
+        .map(|(i, _)| body[..i].chars().count())
+        .min_by_key(|&found| found.abs_diff(start))
+        .map_or((Repair::Unlocatable, start, end), |found| (Repair::Relocated, found, found + length))
+}
+
+/// Check the opinion offsets of each data file against its bodies, writing a
+/// CSV `report` of every opinion that does not match in its file's convention.
+/// With `write`, the files are written there with char offsets throughout,
+/// at the same path relative to `data` as they were read from.
+pub fn validate(
+    data: &Path,
+    files: Vec<(PathBuf, Vec<Value>)>,
+    report: &Path,
+    write: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(report)?;
+    writer.write_record(["file", "record", "opinion", "start", "end", "text", "repair", "char_start", "char_end"])?;
+    if let Some(dir) = write {
+        fs::create_dir_all(dir)?;
+    }
+    for (path, mut records) in files {
+        let relative = path.strip_prefix(data).unwrap_or(&path);
+        let file = relative.to_string_lossy().to_string();
+        let opinions = opinions(&records);
+        let convention = match detect(&opinions) {
+            Some((convention, _)) => convention,
+            None => {
+                if !opinions.is_empty() {
+                    warn!("{}: no opinion matches its body under any offset convention", file);
+                }
+                Convention::Char
+            }
+        };
+        let mut counts = [0; 4];
+        let mut repaired = vec![];
+        for opinion in &opinions {
+            let (repair, start, end) = repair(opinion, convention);
+            counts[match repair {
+                Repair::Matched => 0,
+                Repair::Convention(_) => 1,
+                Repair::Relocated => 2,
+                Repair::Unlocatable => 3,
+            }] += 1;
+            if repair != Repair::Matched {
+                writer.write_record([
+                    file.clone(),
+                    opinion.record.to_string(),
+                    opinion.opinion.to_string(),
+                    opinion.start.to_string(),
+                    opinion.end.to_string(),
+                    opinion.text.to_string(),
+                    repair.as_str().to_string(),
+                    start.to_string(),
+                    end.to_string(),
+                ])?;
+            }
+            repaired.push((opinion.record, opinion.opinion, start, end));
+        }
+        warn!(
+            "{}: {} of {} opinions match as {} offsets, {} in another convention, {} relocated, {} unlocatable",
+            file,
+            counts[0],
+            opinions.len(),
+            convention.as_str(),
+            counts[1],
+            counts[2],
+            counts[3]
+        );
+
+        if let Some(dir) = write {
+            for (record, opinion, start, end) in repaired {
+                let opinion = &mut records[record]["People"]["Opinion"][opinion];
+                opinion["start"] = start.into();
+                opinion["end"] = end.into();
+            }
+            let output = dir.join(relative);
+            if let Some(parent) = output.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            serde_json::to_writer(BufWriter::new(File::create(output)?), &records)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An emoji, a surrogate pair in UTF-16, before CJK text.
+    const BODY: &str = "😀 中国很重要 ok";
+    const TEXT: &str = "中国很重要";
+
+    fn located(body: &'static str, start: usize, end: usize, text: &'static str) -> Located<'static> {
+        Located { record: 0, opinion: 0, body, start, end, text }
+    }
+
+    #[test]
+    fn each_convention_locates_the_text() {
+        for (convention, start, end) in [(Convention::Char, 2, 7), (Convention::Utf16, 3, 8), (Convention::Byte, 5, 20)] {
+            assert!(convention.matches(BODY, start, end, TEXT), "{}", convention.as_str());
+            assert_eq!(convention.to_char(BODY, start), Some(2));
+            assert_eq!(convention.to_char(BODY, end), Some(7));
+            let opinion = located(BODY, start, end, TEXT);
+            assert_eq!(detect(&[opinion]), Some((convention, 1)));
+        }
+    }
+
+    #[test]
+    fn offsets_inside_a_character_do_not_match() {
+        // The middle of the surrogate pair and of a UTF-8 sequence
+        assert_eq!(Convention::Utf16.to_char(BODY, 1), None);
+        assert_eq!(Convention::Byte.to_char(BODY, 6), None);
+        assert!(!Convention::Byte.matches(BODY, 2, 7, TEXT));
+    }
+
+    #[test]
+    fn ascii_ties_prefer_char_offsets() {
+        let opinions = [located("hello world", 6, 11, "world")];
+        assert_eq!(detect(&opinions), Some((Convention::Char, 1)));
+        assert_eq!(repair(&opinions[0], Convention::Char), (Repair::Matched, 6, 11));
+    }
+
+    #[test]
+    fn repair_converts_and_relocates() {
+        assert_eq!(repair(&located(BODY, 3, 8, TEXT), Convention::Byte), (Repair::Convention(Convention::Utf16), 2, 7));
+        assert_eq!(repair(&located(BODY, 0, 1, TEXT), Convention::Char), (Repair::Relocated, 2, 7));
+    }
+
+    #[test]
+    fn missing_text_is_unlocatable() {
+        let opinion = located(BODY, 2, 7, "美国");
+        assert_eq!(detect(std::slice::from_ref(&opinion)), None);
+        assert_eq!(repair(&opinion, Convention::Char), (Repair::Unlocatable, 2, 7));
+        assert_eq!(repair(&located(BODY, 2, 7, ""), Convention::Char).0, Repair::Unlocatable);
+    }
+}
@@ -0,0 +1,136 @@
+use sqlx::{Error, Pool, Postgres, Row};
+
+/// Pick the name and country a person is keyed under.
+///
+/// People are unique by name and country, and are also found through the
+/// names recorded in `people_alias`. A record without a country joins the
+/// single known person of that name, or the one whose title matches. A
+/// record with a country adopts a country-less person with the same title.
+pub async fn resolve(
+    pool: &Pool<Postgres>,
+    name: &str,
+    country_id: Option<i32>,
+    title: Option<&str>,
+) -> Result<(String, Option<i32>), Error> {
+    let rows: Vec<Candidate> = sqlx::query(
+        "SELECT id, name, country_id, title FROM people WHERE name = $1 \
+        UNION SELECT p.id, p.name, p.country_id, p.title FROM people_alias a \
+        JOIN people p ON p.id = a.people_id WHERE a.alias = $1",
+    )
+    .bind(name)
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(|row| (row.get("id"), row.get("name"), row.get("country_id"), row.get("title")))
+    .collect();
+    let resolution = choose(&rows, name, country_id, title);
+    if let Some(id) = resolution.adopt {
+        sqlx::query("UPDATE people SET country_id = $2 WHERE id = $1")
+            .bind(id)
+            .bind(resolution.country_id)
+            .execute(pool)
+            .await?;
+    }
+    Ok((resolution.name, resolution.country_id))
+}
+
+/// A person found under the looked up name: id, name, country and title.
+pub type Candidate = (i32, String, Option<i32>, Option<String>);
+
+/// Outcome of [`choose`]: the key to upsert under, and a country-less person
+/// that takes the record's country.
+pub struct Resolution {
+    pub name: String,
+    pub country_id: Option<i32>,
+    pub adopt: Option<i32>,
+}
+
+/// Apply the rules of [`resolve`] to the people already found under `name`.
+pub fn choose(rows: &[Candidate], name: &str, country_id: Option<i32>, title: Option<&str>) -> Resolution {
+    let same_title = |other: &Option<String>| match (title, other) {
+        (Some(a), Some(b)) => a.trim().eq_ignore_ascii_case(b.trim()),
+        _ => false,
+    };
+    let keep = |n: &str, c: Option<i32>| Resolution {
+        name: n.to_string(),
+        country_id: c,
+        adopt: None,
+    };
+    match country_id {
+        Some(country_id) => {
+            if let Some((_, n, _, _)) = rows.iter().find(|(_, _, c, _)| *c == Some(country_id)) {
+                return keep(n, Some(country_id));
+            }
+            let orphans: Vec<_> = rows
+                .iter()
+                .filter(|(_, _, c, t)| c.is_none() && same_title(t))
+                .collect();
+            if let [(id, n, _, _)] = orphans.as_slice() {
+                return Resolution {
+                    name: n.clone(),
+                    country_id: Some(country_id),
+                    adopt: Some(*id),
+                };
+            }
+            keep(name, Some(country_id))
+        }
+        None => {
+            let known: Vec<_> = rows.iter().filter(|(_, _, c, _)| c.is_some()).collect();
+            if let [(_, n, c, _)] = known.as_slice() {
+                return keep(n, *c);
+            }
+            let titled: Vec<_> = known.into_iter().filter(|(_, _, _, t)| same_title(t)).collect();
+            match titled.as_slice() {
+                [(_, n, c, _)] => keep(n, *c),
+                _ => keep(name, None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: i32, name: &str, country_id: Option<i32>, title: Option<&str>) -> Candidate {
+        (id, name.to_string(), country_id, title.map(str::to_string))
+    }
+
+    fn key(resolution: &Resolution) -> (&str, Option<i32>, Option<i32>) {
+        (resolution.name.as_str(), resolution.country_id, resolution.adopt)
+    }
+
+    #[test]
+    fn new_names_keep_the_record_key() {
+        assert_eq!(key(&choose(&[], "Joe", Some(1), None)), ("Joe", Some(1), None));
+        assert_eq!(key(&choose(&[], "Joe", None, None)), ("Joe", None, None));
+    }
+
+    #[test]
+    fn a_country_joins_the_person_of_that_country_under_the_canonical_name() {
+        // Found through an alias
+        let rows = [candidate(1, "Joe Biden", Some(1), None), candidate(2, "Joe Biden", Some(2), None)];
+        assert_eq!(key(&choose(&rows, "Joe", Some(2), None)), ("Joe Biden", Some(2), None));
+    }
+
+    #[test]
+    fn a_country_adopts_the_single_countryless_person_with_the_title() {
+        let rows = [candidate(1, "Joe", None, Some("Senator")), candidate(2, "Joe", None, Some("Mayor"))];
+        assert_eq!(key(&choose(&rows, "Joe", Some(3), Some(" senator "))), ("Joe", Some(3), Some(1)));
+        // Neither title matches
+        assert_eq!(key(&choose(&rows, "Joe", Some(3), Some("Governor"))), ("Joe", Some(3), None));
+        // Two match
+        let rows = [candidate(1, "Joe", None, Some("Senator")), candidate(2, "Joe", None, Some("Senator"))];
+        assert_eq!(key(&choose(&rows, "Joe", Some(3), Some("Senator"))), ("Joe", Some(3), None));
+    }
+
+    #[test]
+    fn no_country_joins_the_single_known_person_or_the_one_with_the_title() {
+        let rows = [candidate(1, "Joe", Some(1), None), candidate(2, "Joe", None, None)];
+        assert_eq!(key(&choose(&rows, "Joe", None, None)), ("Joe", Some(1), None));
+        let rows = [candidate(1, "Joe", Some(1), Some("Senator")), candidate(2, "Joe", Some(2), Some("Mayor"))];
+        assert_eq!(key(&choose(&rows, "Joe", None, Some("Mayor"))), ("Joe", Some(2), None));
+        // Ambiguous without a title
+        assert_eq!(key(&choose(&rows, "Joe", None, None)), ("Joe", None, None));
+    }
+}
@@ -0,0 +1,80 @@
+/// Annotation stages tracked by the `*_State` flags on a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Peo,
+    Org,
+    Ori,
+    By,
+    PeoExpression,
+    HeadlineClassification,
+    Keywords,
+    Abstract,
+    TopicSentence,
+    TopicSentenceClassification,
+    ExpressionClassification,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 11] = [
+        Stage::Peo,
+        Stage::Org,
+        Stage::Ori,
+        Stage::By,
+        Stage::PeoExpression,
+        Stage::HeadlineClassification,
+        Stage::Keywords,
+        Stage::Abstract,
+        Stage::TopicSentence,
+        Stage::TopicSentenceClassification,
+        Stage::ExpressionClassification,
+    ];
+
+    /// The stage stored as `name` in `article_pipeline_state`.
+    pub fn parse(name: &str) -> Option<Stage> {
+        Stage::ALL.into_iter().find(|s| s.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Peo => "peo",
+            Stage::Org => "org",
+            Stage::Ori => "ori",
+            Stage::By => "by",
+            Stage::PeoExpression => "peo_expression",
+            Stage::HeadlineClassification => "headline_classification",
+            Stage::Keywords => "keywords",
+            Stage::Abstract => "abstract",
+            Stage::TopicSentence => "topic_sentence",
+            Stage::TopicSentenceClassification => "topic_sentence_classification",
+            Stage::ExpressionClassification => "expression_classification",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Done,
+    Failed,
+    Unknown,
+}
+
+impl Status {
+    pub fn parse(raw: &str) -> Status {
+        match raw.trim().to_lowercase().as_str() {
+            "" | "0" | "false" | "no" | "none" | "pending" | "todo" => Status::Pending,
+            "1" | "true" | "yes" | "done" | "finished" | "completed" | "success" => Status::Done,
+            "-1" | "error" | "failed" | "fail" => Status::Failed,
+            _ => Status::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Pending => "pending",
+            Status::Done => "done",
+            Status::Failed => "failed",
+            Status::Unknown => "unknown",
+        }
+    }
+}
@@ -0,0 +1,844 @@
+use async_trait::async_trait;
+use backoff::future::retry;
+use backoff::ExponentialBackoff;
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use sqlx::{Error, Pool, Postgres, Row};
+
+use crate::country::{self, Attributes, Reference};
+use crate::identity::Identity;
+use crate::person;
+use crate::pipeline::{Stage, Status};
+use crate::schema::{Opinion, People, Root, Source};
+use crate::search;
+use crate::sink::{self, Sink};
+use crate::upsert::Policy;
+
+pub async fn create_tables(pool: &Pool<Postgres>) -> Result<(), Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS country (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        name TEXT UNIQUE NOT NULL,
+        iso_alpha2 TEXT UNIQUE,
+        iso_alpha3 TEXT UNIQUE,
+        region TEXT,
+        geography TEXT,
+        belt_and_road BOOLEAN,
+        bri_since DATE,
+        orob_region TEXT,
+        geopolitics TEXT,
+        updated_at TIMESTAMPTZ
+    )",
+    ).execute(pool).await?;
+    // Earlier versions had no reference data
+    sqlx::query(
+        "ALTER TABLE country ADD COLUMN IF NOT EXISTS iso_alpha2 TEXT UNIQUE, \
+        ADD COLUMN IF NOT EXISTS iso_alpha3 TEXT UNIQUE, ADD COLUMN IF NOT EXISTS region TEXT",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "ALTER TABLE country ADD COLUMN IF NOT EXISTS bri_since DATE, \
+        ADD COLUMN IF NOT EXISTS orob_region TEXT, ADD COLUMN IF NOT EXISTS geopolitics TEXT",
+    )
+    .execute(pool)
+    .await?;
+    let countries = Reference::bundled();
+    let countries = countries.countries();
+    sqlx::query(
+        "INSERT INTO country (name, iso_alpha2, iso_alpha3, region) \
+        SELECT * FROM unnest($1::TEXT[], $2::TEXT[], $3::TEXT[], $4::TEXT[]) \
+        ON CONFLICT (name) DO UPDATE SET iso_alpha2 = EXCLUDED.iso_alpha2, \
+        iso_alpha3 = EXCLUDED.iso_alpha3, region = EXCLUDED.region",
+    )
+    .bind(countries.iter().map(|c| c.name).collect::<Vec<_>>())
+    .bind(countries.iter().map(|c| c.alpha2).collect::<Vec<_>>())
+    .bind(countries.iter().map(|c| c.alpha3).collect::<Vec<_>>())
+    .bind(countries.iter().map(|c| c.region).collect::<Vec<_>>())
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS country_conflict (
+        country_id INT NOT NULL,
+        attribute TEXT NOT NULL,
+        stored TEXT NOT NULL,
+        incoming TEXT NOT NULL,
+        UNIQUE (country_id, attribute, stored, incoming),
+        CONSTRAINT fk_country
+            FOREIGN KEY (country_id)
+            REFERENCES country(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS country_unmatched (
+        name TEXT PRIMARY KEY,
+        occurrences INT NOT NULL DEFAULT 1,
+        first_seen TIMESTAMPTZ NOT NULL DEFAULT now()
+    );",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS people (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        name TEXT NOT NULL,
+        country_id INT,
+        title TEXT,
+        origin TEXT,
+        updated_at TIMESTAMPTZ,
+        CONSTRAINT fk_country
+            FOREIGN KEY (country_id)
+            REFERENCES country(id)
+            ON DELETE CASCADE
+    );",
+    )
+
+    .execute(pool)
+    .await?;
+    // Earlier versions kept a single row per name
+    sqlx::query("ALTER TABLE people DROP CONSTRAINT IF EXISTS people_name_key")
+        .execute(pool)
+        .await?;
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS people_name_country ON people (name, COALESCE(country_id, 0))")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS people_alias (
+        people_id INT NOT NULL,
+        alias TEXT NOT NULL,
+        UNIQUE (people_id, alias),
+        CONSTRAINT fk_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS people_account (
+        people_id INT NOT NULL,
+        platform TEXT NOT NULL,
+        account TEXT NOT NULL,
+        url TEXT,
+        screenshot TEXT,
+        UNIQUE (people_id, platform, account),
+        CONSTRAINT fk_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS legislator_term (
+        people_id INT NOT NULL,
+        chamber TEXT,
+        state TEXT,
+        district TEXT,
+        party TEXT,
+        period TEXT,
+        start_year INT,
+        end_year INT,
+        CONSTRAINT fk_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS legislator_term_unique ON legislator_term (
+        people_id,
+        COALESCE(chamber, ''),
+        COALESCE(state, ''),
+        COALESCE(district, ''),
+        COALESCE(party, ''),
+        COALESCE(period, '')
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS source (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        name TEXT UNIQUE NOT NULL,
+        country_id INT,
+        updated_at TIMESTAMPTZ,
+        CONSTRAINT fk_country
+            FOREIGN KEY (country_id)
+            REFERENCES country(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS source_affiliation (
+        source_id INT NOT NULL,
+        kind TEXT NOT NULL,
+        value TEXT NOT NULL,
+        UNIQUE (source_id, kind, value),
+        CONSTRAINT fk_source
+            FOREIGN KEY (source_id)
+            REFERENCES source(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS source_affiliation_kind_value ON source_affiliation (kind, value)")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS article (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        key TEXT UNIQUE NOT NULL,
+        title TEXT,
+        time TIMESTAMPTZ,
+        media_id TEXT,
+        original_site TEXT,
+        abstract TEXT,
+        body TEXT,
+        updated_at TIMESTAMPTZ
+    );",
+    )
+    .execute(pool)
+    .await?;
+    // Earlier versions identified articles by a unique title
+    sqlx::query(
+        "ALTER TABLE article ADD COLUMN IF NOT EXISTS key TEXT, \
+        ADD COLUMN IF NOT EXISTS media_id TEXT, ADD COLUMN IF NOT EXISTS original_site TEXT, \
+        DROP CONSTRAINT IF EXISTS article_title_key, ALTER COLUMN title DROP NOT NULL",
+    )
+    .execute(pool)
+    .await?;
+    backfill_article_keys(pool).await?;
+    sqlx::query("ALTER TABLE article ALTER COLUMN key SET NOT NULL")
+        .execute(pool)
+        .await?;
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS article_key_key ON article (key)")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS source_article (\
+        source_id INT NOT NULL,
+        article_id INT NOT NULL,
+        UNIQUE (source_id, article_id),
+        CONSTRAINT fk_source
+            FOREIGN KEY (source_id)
+            REFERENCES source(id)
+            ON DELETE CASCADE,
+        CONSTRAINT fk_article
+            FOREIGN KEY (article_id)
+            REFERENCES article(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS opinion (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        author_id INT NOT NULL,
+        text TEXT UNIQUE NOT NULL,
+        article_id INT NOT NULL,
+        CONSTRAINT fk_author
+            FOREIGN KEY (author_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE,
+        CONSTRAINT fk_article
+            FOREIGN KEY (article_id)
+            REFERENCES article(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS article_pipeline_state (
+        article_id INT NOT NULL,
+        stage TEXT NOT NULL,
+        status TEXT NOT NULL CHECK (status IN ('pending', 'done', 'failed', 'unknown')),
+        raw TEXT,
+        UNIQUE (article_id, stage),
+        CONSTRAINT fk_article
+            FOREIGN KEY (article_id)
+            REFERENCES article(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS identity (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        name TEXT UNIQUE NOT NULL
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS people_identity (
+        people_id INT NOT NULL,
+        identity_id INT NOT NULL,
+        value TEXT NOT NULL,
+        UNIQUE (people_id, identity_id, value),
+        CONSTRAINT fk_people
+            FOREIGN KEY (people_id)
+            REFERENCES people(id)
+            ON DELETE CASCADE,
+        CONSTRAINT fk_identity
+            FOREIGN KEY (identity_id)
+            REFERENCES identity(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("INSERT INTO identity (name) SELECT unnest($1::TEXT[]) ON CONFLICT DO NOTHING")
+        .bind(Identity::ALL.iter().map(|i| i.as_str()).collect::<Vec<_>>())
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS source_alias (
+        source_id INT NOT NULL,
+        alias TEXT NOT NULL,
+        UNIQUE (source_id, alias),
+        CONSTRAINT fk_source
+            FOREIGN KEY (source_id)
+            REFERENCES source(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS entity_audit (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        action TEXT NOT NULL,
+        entity TEXT NOT NULL,
+        target_id INT NOT NULL,
+        other_id INT NOT NULL,
+        detail JSONB,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS import_run (
+        id INT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+        started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        finished_at TIMESTAMPTZ
+    );",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS raw_record (
+        hash TEXT PRIMARY KEY,
+        id BIGINT UNIQUE GENERATED ALWAYS AS IDENTITY,
+        record JSONB NOT NULL,
+        run_id INT NOT NULL,
+        file TEXT NOT NULL,
+        position INT NOT NULL,
+        CONSTRAINT fk_run
+            FOREIGN KEY (run_id)
+            REFERENCES import_run(id)
+            ON DELETE CASCADE
+    );",
+    )
+    .execute(pool)
+    .await?;
+    for table in ["country", "source", "people", "article"] {
+        sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ"))
+            .execute(pool)
+            .await?;
+    }
+    // Earlier versions stored times as epoch seconds, with 0 for unparseable dates
+    for (table, column) in [
+        ("country", "updated_at"),
+        ("source", "updated_at"),
+        ("people", "updated_at"),
+        ("article", "updated_at"),
+        ("article", "time"),
+    ] {
+        let Some(row) = sqlx::query(
+            "SELECT data_type FROM information_schema.columns \
+            WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2",
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_optional(pool)
+        .await?
+        else {
+            continue;
+        };
+        let data_type = row.get::<String, _>("data_type");
+        if data_type == "integer" || data_type == "bigint" {
+            warn!("Converting {}.{} to TIMESTAMPTZ", table, column);
+            sqlx::query(&format!(
+                "ALTER TABLE {table} ALTER COLUMN {column} DROP NOT NULL, \
+                ALTER COLUMN {column} TYPE TIMESTAMPTZ USING to_timestamp(NULLIF({column}, 0))"
+            ))
+            .execute(pool)
+            .await?;
+            if (table, column) == ("article", "time") {
+                // It held the record's Update_Time rather than when the article was published
+                sqlx::query("UPDATE article SET updated_at = COALESCE(updated_at, time), time = NULL")
+                    .execute(pool)
+                    .await?;
+            }
+        }
+    }
+    sqlx::query("ALTER TABLE article ADD COLUMN IF NOT EXISTS abstract TEXT, ADD COLUMN IF NOT EXISTS body TEXT")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "ALTER TABLE opinion ADD COLUMN IF NOT EXISTS score DOUBLE PRECISION, \
+        ADD COLUMN IF NOT EXISTS span_start BIGINT, ADD COLUMN IF NOT EXISTS span_end BIGINT",
+    )
+    .execute(pool)
+    .await?;
+    search::create_indexes(pool, &search::config_from_env()).await?;
+    Ok(())
+}
+
+/// Marks the key of articles stored before keys existed, which were unique by
+/// title. The raw fields a key derives from were not stored, so these rows
+/// take the title after `LEGACY_KEY` until `PgSink` sees the article again.
+const LEGACY_KEY: &str = "legacy:";
+
+async fn backfill_article_keys(pool: &Pool<Postgres>) -> Result<(), Error> {
+    let backfilled = sqlx::query("UPDATE article SET key = $1 || COALESCE(title, '#' || id) WHERE key IS NULL")
+        .bind(LEGACY_KEY)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if backfilled > 0 {
+        warn!("Gave {} articles legacy keys until they are imported again", backfilled);
+    }
+    Ok(())
+}
+
+/// Writes records into the Postgres schema, retrying each statement.
+pub struct PgSink {
+    pool: Pool<Postgres>,
+    country_sql: String,
+    source_sql: String,
+    article_sql: String,
+    people_sql: String,
+}
+
+impl PgSink {
+    pub fn new(pool: Pool<Postgres>) -> PgSink {
+        PgSink {
+            pool,
+            // Countries are seeded from the reference data, so only fill in what they lack by default
+            country_sql: format!(
+                "INSERT INTO country (name, geography, belt_and_road, bri_since, orob_region, geopolitics, updated_at) \
+                VALUES ($1, $2, $3, $4, $5, $6, $7) {} RETURNING id",
+                Policy::from_env("country", Policy::MergeNonNull).on_conflict(
+                    "country",
+                    "name",
+                    &["geography", "belt_and_road", "bri_since", "orob_region", "geopolitics"],
+                ),
+            ),
+            source_sql: format!(
+                "INSERT INTO source (name, country_id, updated_at) VALUES ($1, $2, $3) {} RETURNING id",
+                Policy::from_env("source", Policy::FirstWins).on_conflict("source", "name", &["country_id"]),
+            ),
+            article_sql: format!(
+                "INSERT INTO article (key, title, time, media_id, original_site, abstract, body, updated_at) \
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8) {} RETURNING id",
+                Policy::from_env("article", Policy::FirstWins).on_conflict(
+                    "article",
+                    "key",
+                    &["title", "time", "media_id", "original_site", "abstract", "body"],
+                ),
+            ),
+            people_sql: format!(
+                "INSERT INTO people (name, country_id, origin, title, updated_at) VALUES ($1, $2, $3, $4, $5) {} RETURNING id",
+                Policy::from_env("people", Policy::FirstWins).on_conflict("people", "name, (COALESCE(country_id, 0))", &["origin", "title"]),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for PgSink {
+    async fn record_unmatched_country(&self, raw: &str) -> sink::Result<bool> {
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(country::report_unmatched(&self.pool, raw).await.inspect_err(|e| error!("1. {:?}", e))?)
+        })
+            .await?)
+    }
+
+    async fn record_country_conflicts(
+        &self,
+        name: &str,
+        attributes: &Attributes<'_>,
+    ) -> sink::Result<Vec<(&'static str, String, String)>> {
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(country::record_conflicts(&self.pool, name, attributes).await.inspect_err(|e| error!("1. {:?}", e))?)
+        })
+            .await?)
+    }
+
+    async fn resolve_country(
+        &self,
+        name: &str,
+        attributes: &Attributes<'_>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(
+                match sqlx::query(&self.country_sql)
+                    .bind(name)
+                    .bind(attributes.geography)
+                    .bind(attributes.orob.map(|o| o.member))
+                    .bind(attributes.orob.and_then(|o| o.since))
+                    .bind(attributes.orob_region)
+                    .bind(attributes.geopolitics)
+                    .bind(updated_at)
+                    .fetch_one(&self.pool)
+                    .await
+                {
+                    Ok(row) => row.get::<i32, _>("id"),
+                    Err(e1) => sqlx::query("SELECT id FROM country WHERE name = $1")
+                        .bind(name)
+                        .fetch_one(&self.pool)
+                        .await.inspect_err(|e2| error!("1. {:?}, 2. {:?}", e1, e2))?
+                        .get::<i32, _>("id"),
+                }
+            )
+        })
+            .await?)
+    }
+
+    async fn resolve_source(&self, name: &str, country_id: Option<i32>, updated_at: Option<DateTime<Utc>>) -> sink::Result<i32> {
+        let name = retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "SELECT s.name FROM source_alias a JOIN source s ON s.id = a.source_id WHERE a.alias = $1",
+            )
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await.inspect_err(|e| error!("1. {:?}", e))?
+                .map_or(name.to_string(), |row| row.get::<String, _>("name")))
+        })
+            .await?;
+        let id = retry(ExponentialBackoff::default(), || async {
+            Ok(
+                match sqlx::query(&self.source_sql)
+                    .bind(&name)
+                    .bind(country_id)
+                    .bind(updated_at)
+                    .fetch_one(&self.pool)
+                    .await
+                {
+                    Ok(row) => row.get::<i32, _>("id"),
+                    Err(e1) => sqlx::query("SELECT id FROM source WHERE name = $1")
+                        .bind(&name)
+                        .fetch_one(&self.pool)
+                        .await.inspect_err(|e2| error!("1. {:?}, 2. {:?}", e1, e2))?
+                        .get::<i32, _>("id"),
+                }
+            )
+        })
+            .await?;
+        Ok(id)
+    }
+
+    async fn add_source_details(&self, source_id: i32, source: &Source) -> sink::Result<()> {
+        for (affiliation, value) in source.affiliations() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO source_affiliation (source_id, kind, value) \
+                    VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                )
+                    .bind(source_id)
+                    .bind(affiliation.as_str())
+                    .bind(value)
+                    .execute(&self.pool)
+                    .await.inspect_err(|e| error!("1. {:?}", e))?)
+            })
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn upsert_article(
+        &self,
+        key: &str,
+        root: &Root,
+        time: Option<DateTime<Utc>>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        Ok(retry(ExponentialBackoff::default(), || async {
+            // Take over the legacy row of this title the first time it is seen
+            if let Some(title) = &root.headline {
+                sqlx::query(
+                    "UPDATE article SET key = $1 WHERE key = $2 || $3 \
+                    AND NOT EXISTS (SELECT 1 FROM article WHERE key = $1)",
+                )
+                .bind(key)
+                .bind(LEGACY_KEY)
+                .bind(title)
+                .execute(&self.pool)
+                .await?;
+            }
+            Ok(
+                match sqlx::query(&self.article_sql)
+                    .bind(key)
+                    .bind(&root.headline)
+                    .bind(time)
+                    .bind(&root.media_id)
+                    .bind(&root.original_site)
+                    .bind(&root.abstract_field)
+                    .bind(&root.body)
+                    .bind(updated_at)
+                    .fetch_one(&self.pool)
+                    .await
+                {
+                    Ok(row) => row.get::<i32, _>("id"),
+                    Err(e1) => sqlx::query("SELECT id FROM article WHERE key = $1")
+                        .bind(key)
+                        .fetch_one(&self.pool)
+                        .await.inspect_err(|e2| error!("1. {:?}, 2. {:?}", e1, e2))?
+                        .get::<i32, _>("id"),
+                }
+            )
+        })
+            .await?)
+    }
+
+    async fn set_pipeline_state(&self, article_id: i32, stage: Stage, status: Status, raw: &str) -> sink::Result<()> {
+        retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "INSERT INTO article_pipeline_state (article_id, stage, status, raw) \
+                VALUES ($1, $2, $3, $4) ON CONFLICT (article_id, stage) \
+                DO UPDATE SET status = EXCLUDED.status, raw = EXCLUDED.raw",
+            )
+                .bind(article_id)
+                .bind(stage.as_str())
+                .bind(status.as_str())
+                .bind(raw)
+                .execute(&self.pool)
+                .await.inspect_err(|e| error!("1. {:?}", e))?)
+        })
+            .await?;
+        Ok(())
+    }
+
+    async fn link_source(&self, source_id: i32, article_id: i32) -> sink::Result<()> {
+        retry(ExponentialBackoff::default(), || async {
+            Ok(sqlx::query(
+                "INSERT INTO source_article (source_id, article_id) \
+                VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            )
+                .bind(source_id)
+                .bind(article_id)
+                .execute(&self.pool)
+                .await.inspect_err(|e| error!("1. {:?}", e))?)
+        })
+            .await?;
+        Ok(())
+    }
+
+    async fn resolve_person(
+        &self,
+        name: &str,
+        country_id: Option<i32>,
+        people: &People,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> sink::Result<i32> {
+        let (name, country_id) = retry(ExponentialBackoff::default(), || async {
+            Ok(person::resolve(&self.pool, name, country_id, people.title.as_deref())
+                .await
+                .inspect_err(|e| error!("1. {:?}", e))?)
+        })
+            .await?;
+        Ok(retry(ExponentialBackoff::default(), || async {
+            Ok(
+                match sqlx::query(&self.people_sql)
+                    .bind(&name)
+                    .bind(country_id)
+                    .bind(people.get_from())
+                    .bind(&people.title)
+                    .bind(updated_at)
+                    .fetch_one(&self.pool)
+                    .await
+                {
+                    Ok(row) => row.get::<i32, _>("id"),
+                    Err(e1) => sqlx::query(
+                        "SELECT id FROM people WHERE name = $1 AND COALESCE(country_id, 0) = COALESCE($2, 0)",
+                    )
+                        .bind(&name)
+                        .bind(country_id)
+                        .fetch_one(&self.pool)
+                        .await.inspect_err(|e2| error!("1. {:?}, 2. {:?}", e1, e2))?
+                        .get::<i32, _>("id"),
+                }
+            )
+        })
+            .await?)
+    }
+
+    async fn add_person_details(&self, people_id: i32, people: &People) -> sink::Result<()> {
+        for alias in [people.name.as_deref(), people.name_clean.as_deref()].into_iter().flatten() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO people_alias (people_id, alias) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                )
+                    .bind(people_id)
+                    .bind(alias)
+                    .execute(&self.pool)
+                    .await.inspect_err(|e| error!("1. {:?}", e))?)
+            })
+                .await?;
+        }
+
+        for (identity, value) in people.identities() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO people_identity (people_id, identity_id, value) \
+                    SELECT $1, id, $3 FROM identity WHERE name = $2 ON CONFLICT DO NOTHING",
+                )
+                    .bind(people_id)
+                    .bind(identity.as_str())
+                    .bind(value)
+                    .execute(&self.pool)
+                    .await.inspect_err(|e| error!("1. {:?}", e))?)
+            })
+                .await?;
+        }
+
+        for account in people.accounts() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO people_account (people_id, platform, account, url, screenshot) \
+                    VALUES ($1, $2, $3, $4, $5) ON CONFLICT (people_id, platform, account) \
+                    DO UPDATE SET url = COALESCE(people_account.url, EXCLUDED.url), \
+                    screenshot = COALESCE(people_account.screenshot, EXCLUDED.screenshot)",
+                )
+                    .bind(people_id)
+                    .bind(account.platform.as_str())
+                    .bind(&account.account)
+                    .bind(account.url)
+                    .bind(account.screenshot)
+                    .execute(&self.pool)
+                    .await.inspect_err(|e| error!("1. {:?}", e))?)
+            })
+                .await?;
+        }
+
+        if let Some(term) = people.legislator_term() {
+            retry(ExponentialBackoff::default(), || async {
+                Ok(sqlx::query(
+                    "INSERT INTO legislator_term \
+                    (people_id, chamber, state, district, party, period, start_year, end_year) \
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT DO NOTHING",
+                )
+                    .bind(people_id)
+                    .bind(term.chamber)
+                    .bind(term.state)
+                    .bind(term.district)
+                    .bind(term.party)
+                    .bind(term.period)
+                    .bind(term.start_year)
+                    .bind(term.end_year)
+                    .execute(&self.pool)
+                    .await.inspect_err(|e| error!("1. {:?}", e))?)
+            })
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn add_opinion(&self, author_id: i32, article_id: i32, opinion: &Opinion) -> sink::Result<()> {
+        retry(ExponentialBackoff::default(), || async {
+            let result = sqlx::query(
+                "INSERT INTO opinion (author_id, text, article_id, score, span_start, span_end) \
+                        VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+            )
+                .bind(author_id)
+                .bind(&opinion.text)
+                .bind(article_id)
+                .bind(opinion.score)
+                .bind(opinion.start)
+                .bind(opinion.end)
+                .execute(&self.pool)
+                .await;
+            Ok(
+                match result {
+                    Err(e) => {
+                        let error = e.as_database_error().unwrap();
+                        match error.code().unwrap().as_ref() {
+                            // Text too long for the unique index
+                            "54000" => Ok(()),
+                            _ => Err(e),
+                        }
+                    }
+                    Ok(_) => Ok(()),
+                }.inspect_err(|e| error!("1. {:?}", e))?,
+            )
+        })
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+    use crate::article::ArticleKey;
+
+    /// Needs a database at `TEST_POSTGRES_URL`, and is skipped without one.
+    #[tokio::test]
+    async fn legacy_articles_are_rekeyed_on_reimport() {
+        let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+            return;
+        };
+        // One connection, so the search path holds for every statement
+        let pool = PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap();
+        for sql in [
+            "DROP SCHEMA IF EXISTS legacy_key_test CASCADE",
+            "CREATE SCHEMA legacy_key_test",
+            "SET search_path TO legacy_key_test",
+            "CREATE TABLE article (id SERIAL PRIMARY KEY, title TEXT UNIQUE NOT NULL, time INT NOT NULL)",
+            "INSERT INTO article (title, time) VALUES ('Old headline', 1672531200)",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        create_tables(&pool).await.unwrap();
+        let (id, key): (i32, String) = sqlx::query_as("SELECT id, key FROM article").fetch_one(&pool).await.unwrap();
+        assert_eq!(key, "legacy:Old headline");
+        let (time, updated_at): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) =
+            sqlx::query_as("SELECT time, updated_at FROM article").fetch_one(&pool).await.unwrap();
+        assert_eq!(time, None);
+        assert_eq!(updated_at, DateTime::from_timestamp(1672531200, 0));
+
+        let root = Root {
+            headline: Some("Old headline".to_string()),
+            media_id: Some("m1".to_string()),
+            ..Default::default()
+        };
+        let hash = ArticleKey::Hash.derive(&root).unwrap();
+        let sink = PgSink::new(pool.clone());
+        assert_eq!(sink.upsert_article(&hash, &root, None, None).await.unwrap(), id);
+        assert_eq!(sink.upsert_article(&hash, &root, None, None).await.unwrap(), id);
+        let keys: Vec<(String,)> = sqlx::query_as("SELECT key FROM article").fetch_all(&pool).await.unwrap();
+        assert_eq!(keys, vec![(hash,)]);
+
+        sqlx::query("DROP SCHEMA legacy_key_test CASCADE").execute(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use log::warn;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{Error, Pool, Postgres, Row};
+
+use crate::schema::Root;
+
+/// Records archived per statement.
+const BATCH: usize = 1000;
+
+/// SHA-256 of the record with its keys sorted, so reordered keys hash alike.
+pub fn hash(record: &Value) -> String {
+    // serde_json keeps object keys in a BTreeMap, which serialises them sorted
+    format!("{:x}", Sha256::digest(record.to_string().as_bytes()))
+}
+
+pub async fn start_run(pool: &Pool<Postgres>) -> Result<i32, Error> {
+    Ok(sqlx::query("INSERT INTO import_run DEFAULT VALUES RETURNING id")
+        .fetch_one(pool)
+        .await?
+        .get("id"))
+}
+
+pub async fn finish_run(pool: &Pool<Postgres>, run_id: i32) -> Result<(), Error> {
+    sqlx::query("UPDATE import_run SET finished_at = now() WHERE id = $1")
+        .bind(run_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Store `records` of `file` verbatim, returning how many were not archived before.
+/// A record seen again keeps the run and file it first came from.
+pub async fn archive(pool: &Pool<Postgres>, run_id: i32, file: &str, records: &[Value]) -> Result<u64, Error> {
+    let mut archived = 0;
+    for (chunk, records) in records.chunks(BATCH).enumerate() {
+        archived += sqlx::query(
+            "INSERT INTO raw_record (hash, record, run_id, file, position) \
+            SELECT hash, record::JSONB, $3, $4, position FROM unnest($1::TEXT[], $2::TEXT[], $5::INT[]) \
+            AS r (hash, record, position) \
+            ON CONFLICT (hash) DO NOTHING",
+        )
+        .bind(records.iter().map(hash).collect::<Vec<_>>())
+        .bind(records.iter().map(Value::to_string).collect::<Vec<_>>())
+        .bind(run_id)
+        .bind(file)
+        .bind((0..records.len()).map(|i| (chunk * BATCH + i) as i32).collect::<Vec<_>>())
+        .execute(pool)
+        .await?
+        .rows_affected();
+    }
+    Ok(archived)
+}
+
+/// Archived records after `after` in the order they were first imported, with their ids.
+pub async fn page(pool: &Pool<Postgres>, after: i64, limit: i64) -> Result<Vec<(i64, Value)>, Error> {
+    sqlx::query("SELECT id, record::TEXT AS record FROM raw_record WHERE id > $1 ORDER BY id LIMIT $2")
+        .bind(after)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let record = serde_json::from_str(row.get("record")).map_err(|e| Error::Decode(e.into()))?;
+            Ok((row.get("id"), record))
+        })
+        .collect()
+}
+
+/// Deserialise records into `Root`, skipping those that do not fit the schema.
+pub fn roots(records: Vec<Value>) -> Vec<Root> {
+    records
+        .into_iter()
+        .filter_map(|record| {
+            serde_json::from_value(record)
+                .inspect_err(|e| warn!("Skip record not matching the schema: {}", e))
+                .ok()
+        })
+        .collect()
+}
@@ -0,0 +1,436 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use log::{error, warn};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+
+use crate::schema::Root;
+use crate::timestamp::TimeParser;
+
+/// The part of a record a rule checks. Fields are named as in the JSON files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    Root,
+    People,
+    Source,
+    Opinion,
+}
+
+impl Target {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Target::Root => "root",
+            Target::People => "people",
+            Target::Source => "source",
+            Target::Opinion => "opinion",
+        }
+    }
+
+    /// The objects of the serialised `record` this target covers.
+    fn objects<'a>(&self, record: &'a Value) -> Vec<&'a Value> {
+        let all = |value: &'a Value| value.as_array().map(|a| a.iter().collect()).unwrap_or_default();
+        match self {
+            Target::Root => vec![record],
+            Target::People => vec![&record["People"]],
+            Target::Source => all(&record["Source"]),
+            Target::Opinion => all(&record["People"]["Opinion"]),
+        }
+    }
+}
+
+/// What happens to a record breaking a rule, from mildest to harshest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Log the violation and load the record.
+    #[default]
+    Warn,
+    /// Write the record to the quarantine file instead of loading it.
+    Quarantine,
+    /// Stop the run before any record is loaded.
+    Abort,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Op {
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = ">")]
+    Gt,
+}
+
+impl Op {
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Ge => ordering.is_ge(),
+            Op::Gt => ordering.is_gt(),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Ge => ">=",
+            Op::Gt => ">",
+        }
+    }
+}
+
+/// A comparison with another field of the same object.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Compare {
+    pub op: Op,
+    pub field: String,
+}
+
+/// Constraints on one field. Absent values only fail `required`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Names the rule in logs, `<target>.<field>` by default.
+    pub name: Option<String>,
+    pub target: Target,
+    pub field: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// The field is present and not blank.
+    #[serde(default)]
+    pub required: bool,
+    /// The field matches this regular expression somewhere, anchor it to match all of it.
+    #[serde(default, deserialize_with = "regex")]
+    pub pattern: Option<Regex>,
+    /// The field is a number no less than this.
+    pub min: Option<f64>,
+    /// The field is a number no greater than this.
+    pub max: Option<f64>,
+    /// The field is one of these strings.
+    pub values: Option<Vec<String>>,
+    pub compare: Option<Compare>,
+    /// The field is a time no later than now.
+    #[serde(default)]
+    pub not_future: bool,
+}
+
+fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map(Some).map_err(serde::de::Error::custom)
+}
+
+impl Rule {
+    fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("{}.{}", self.target.as_str(), self.field))
+    }
+
+    /// Why `object` breaks this rule, if it does.
+    fn check(&self, object: &Value, times: &TimeParser) -> Vec<String> {
+        let value = &object[&self.field];
+        let blank = match value {
+            Value::Null => true,
+            Value::String(s) => s.trim().is_empty(),
+            _ => false,
+        };
+        if blank {
+            return if self.required { vec!["is missing".to_string()] } else { vec![] };
+        }
+
+        let mut problems = vec![];
+        let text = match value {
+            Value::String(s) => s.clone(),
+            value => value.to_string(),
+        };
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(&text) {
+                problems.push(format!("{:?} does not match {}", text, pattern));
+            }
+        }
+        if self.min.is_some() || self.max.is_some() {
+            match number(value) {
+                Some(n) if self.min.is_some_and(|min| n < min) || self.max.is_some_and(|max| n > max) => {
+                    problems.push(format!("{} is outside [{}, {}]", n, bound(self.min), bound(self.max)))
+                }
+                Some(_) => {}
+                None => problems.push(format!("{:?} is not a number", text)),
+            }
+        }
+        if let Some(values) = &self.values {
+            if !values.contains(&text) {
+                problems.push(format!("{:?} is not one of {:?}", text, values));
+            }
+        }
+        if let Some(compare) = &self.compare {
+            let other = &object[&compare.field];
+            if let Some(ordering) = order(value, other, times) {
+                if !compare.op.holds(ordering) {
+                    problems.push(format!(
+                        "{} {} {} does not hold for {} and {}",
+                        self.field,
+                        compare.op.as_str(),
+                        compare.field,
+                        value,
+                        other
+                    ));
+                }
+            }
+        }
+        if self.not_future {
+            if let Some(time) = times.parse(&text).filter(|time| *time > Utc::now()) {
+                problems.push(format!("{} is in the future", time));
+            }
+        }
+        problems
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn bound(bound: Option<f64>) -> String {
+    bound.map_or("..".to_string(), |b| b.to_string())
+}
+
+/// Compare two present values as numbers, then as times, then as strings.
+fn order(a: &Value, b: &Value, times: &TimeParser) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (number(a), number(b)) {
+        return a.partial_cmp(&b);
+    }
+    let (Value::String(a), Value::String(b)) = (a, b) else {
+        return None;
+    };
+    match (times.parse(a), times.parse(b)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => Some(a.cmp(b)),
+    }
+}
+
+fn headline(root: &Root) -> String {
+    root.headline.clone().unwrap_or_default()
+}
+
+fn default_quarantine() -> PathBuf {
+    PathBuf::from("quarantine.ndjson")
+}
+
+/// Validation rules applied to records before they are loaded.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    /// Quarantined records are appended here, one JSON object per line.
+    #[serde(default = "default_quarantine")]
+    pub quarantine: PathBuf,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// Read the TOML file at `RULES_FILE`, or no rules when it is unset. For example
+    ///
+    /// ```toml
+    /// quarantine = "quarantine.ndjson"
+    ///
+    /// [[rule]]
+    /// target = "opinion"
+    /// field = "score"
+    /// min = -1.0
+    /// max = 1.0
+    /// severity = "quarantine"
+    ///
+    /// [[rule]]
+    /// target = "opinion"
+    /// field = "end"
+    /// compare = { op = ">=", field = "start" }
+    /// ```
+    pub fn from_env() -> Rules {
+        let Ok(path) = std::env::var("RULES_FILE") else {
+            return Rules::default();
+        };
+        let rules = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Cannot read RULES_FILE {}: {}", path, e));
+        toml::from_str(&rules).unwrap_or_else(|e| panic!("Invalid RULES_FILE {}: {}", path, e))
+    }
+
+    /// Whether any rule stops the run, so records must be checked before loading.
+    pub fn aborts(&self) -> bool {
+        self.rules.iter().any(|rule| rule.severity == Severity::Abort)
+    }
+
+    /// How the serialised `record` breaks the rules of at least `severity`.
+    fn violations(&self, record: &Value, severity: Severity, times: &TimeParser) -> Vec<(Severity, String)> {
+        let mut violations = vec![];
+        for rule in self.rules.iter().filter(|rule| rule.severity >= severity) {
+            for (i, object) in rule.target.objects(record).into_iter().enumerate() {
+                for problem in rule.check(object, times) {
+                    let at = match rule.target {
+                        Target::Source | Target::Opinion => format!("{}[{}]", rule.name(), i),
+                        Target::Root | Target::People => rule.name(),
+                    };
+                    violations.push((rule.severity, format!("{}: {}", at, problem)));
+                }
+            }
+        }
+        violations
+    }
+
+    /// Log every violation of an abort rule in `roots`, failing if there is one.
+    /// Run over all records before loading any, so an abort leaves nothing behind.
+    pub fn check_aborts(&self, roots: &[Root], times: &TimeParser) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut aborted = 0;
+        for root in roots {
+            let violations = self.violations(&serde_json::to_value(root)?, Severity::Abort, times);
+            for (_, violation) in &violations {
+                error!("Rule violated by {:?}: {}", headline(root), violation);
+            }
+            aborted += !violations.is_empty() as usize;
+        }
+        if aborted > 0 {
+            return Err(format!("{} records break abort rules", aborted).into());
+        }
+        Ok(())
+    }
+
+    /// The `roots` that may be loaded. Violations are logged, and records
+    /// breaking a quarantine rule are written to the quarantine file. Abort
+    /// rules should have been checked for the whole run with `check_aborts`,
+    /// a batch still breaking one fails before anything is written.
+    pub fn apply(&self, roots: Vec<Root>, times: &TimeParser) -> Result<Vec<Root>, Box<dyn Error + Send + Sync>> {
+        if self.rules.is_empty() {
+            return Ok(roots);
+        }
+        let mut checked = vec![];
+        for root in roots {
+            let record = serde_json::to_value(&root)?;
+            let violations = self.violations(&record, Severity::Warn, times);
+            checked.push((root, record, violations));
+        }
+        if checked.iter().any(|(_, _, violations)| violations.iter().any(|(s, _)| *s == Severity::Abort)) {
+            let roots: Vec<Root> = checked.into_iter().map(|(root, _, _)| root).collect();
+            return self.check_aborts(&roots, times).map(|_| vec![]);
+        }
+
+        let mut passed = vec![];
+        let mut quarantined = 0;
+        let mut quarantine = None;
+        for (root, record, violations) in checked {
+            for (_, violation) in &violations {
+                warn!("Rule violated by {:?}: {}", headline(&root), violation);
+            }
+            if violations.iter().any(|(severity, _)| *severity == Severity::Quarantine) {
+                if quarantine.is_none() {
+                    quarantine = Some(OpenOptions::new().create(true).append(true).open(&self.quarantine)?);
+                }
+                let violations: Vec<&String> = violations.iter().map(|(_, violation)| violation).collect();
+                let line = json!({ "violations": violations, "record": record });
+                writeln!(quarantine.as_mut().unwrap(), "{}", line)?;
+                quarantined += 1;
+            } else {
+                passed.push(root);
+            }
+        }
+        if quarantined > 0 {
+            warn!("Quarantined {} records to {}", quarantined, self.quarantine.display());
+        }
+        Ok(passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(toml: &str) -> Rule {
+        toml::from_str(&format!("target = \"opinion\"\nfield = \"value\"\n{}", toml)).unwrap()
+    }
+
+    fn check(rule: &Rule, object: Value) -> Vec<String> {
+        rule.check(&object, &TimeParser::from_env())
+    }
+
+    #[test]
+    fn range() {
+        let score = rule("min = -1.0\nmax = 1.0");
+        assert!(check(&score, json!({ "value": 0.5 })).is_empty());
+        assert!(check(&score, json!({ "value": -1 })).is_empty());
+        assert_eq!(check(&score, json!({ "value": 1.5 })), ["1.5 is outside [-1, 1]"]);
+        assert_eq!(check(&score, json!({ "value": "-2" })).len(), 1);
+        assert_eq!(check(&score, json!({ "value": "high" })), ["\"high\" is not a number"]);
+        assert!(check(&rule("min = 0.0"), json!({ "value": 1e9 })).is_empty());
+    }
+
+    #[test]
+    fn pattern() {
+        let name = rule("pattern = \"^[A-Z][a-z]+$\"");
+        assert!(check(&name, json!({ "value": "Smith" })).is_empty());
+        assert_eq!(check(&name, json!({ "value": "smith" })).len(), 1);
+        assert!(toml::from_str::<Rule>("target = \"root\"\nfield = \"Time\"\npattern = \"(\"").is_err());
+    }
+
+    #[test]
+    fn values() {
+        let platform = rule("values = [\"twitter\", \"facebook\"]");
+        assert!(check(&platform, json!({ "value": "twitter" })).is_empty());
+        assert_eq!(check(&platform, json!({ "value": "myspace" })).len(), 1);
+    }
+
+    #[test]
+    fn compare_numbers() {
+        let end = rule("compare = { op = \">=\", field = \"start\" }");
+        assert!(check(&end, json!({ "value": 10, "start": 2 })).is_empty());
+        assert!(check(&end, json!({ "value": 2, "start": 2 })).is_empty());
+        assert_eq!(check(&end, json!({ "value": 3, "start": 9 })), ["value >= start does not hold for 3 and 9"]);
+        // Nothing to compare with
+        assert!(check(&end, json!({ "value": 3 })).is_empty());
+    }
+
+    #[test]
+    fn compare_times() {
+        let updated = rule("compare = { op = \">\", field = \"created\" }");
+        assert!(check(&updated, json!({ "value": "2023-02-01", "created": "2023-01-15" })).is_empty());
+        // Compared as times, although as text "2023/01/02" sorts after "2023-01-15"
+        assert_eq!(check(&updated, json!({ "value": "2023/01/02", "created": "2023-01-15" })).len(), 1);
+        assert_eq!(check(&updated, json!({ "value": "2023-01-01", "created": "2023-01-15" })).len(), 1);
+    }
+
+    #[test]
+    fn not_future() {
+        let time = rule("not_future = true");
+        assert!(check(&time, json!({ "value": "2020-01-01" })).is_empty());
+        assert_eq!(check(&time, json!({ "value": "2999-01-01" })).len(), 1);
+        // Unparseable times are reported elsewhere
+        assert!(check(&time, json!({ "value": "soon" })).is_empty());
+    }
+
+    #[test]
+    fn blank_values_only_fail_required() {
+        let required = rule("required = true\nmin = 0.0");
+        for blank in [json!({}), json!({ "value": null }), json!({ "value": "  " })] {
+            assert_eq!(check(&required, blank.clone()), ["is missing"]);
+            assert!(check(&rule("min = 0.0\npattern = \"x\""), blank).is_empty());
+        }
+    }
+}
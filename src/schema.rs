@@ -1,6 +1,13 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::account::{twitter_handle, url_account, Account, Platform};
+use crate::affiliation::Affiliation;
+use crate::country::{parse_orob, Attributes};
+use crate::identity::Identity;
+use crate::legislator::{parse_period, Term};
+use crate::pipeline::Stage;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Root {
@@ -150,9 +157,14 @@ pub struct People {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Opinion {
-    pub score: f64,
-    pub start: i64,
-    pub end: i64,
+    /// Missing from rows stored before scores and spans were kept, and then
+    /// left out of exports rather than written as zero.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<i64>,
     pub text: Option<String>,
 }
 
@@ -217,61 +229,163 @@ pub struct Source {
     pub from_university_news: Option<String>,
 }
 
+impl Root {
+    pub fn pipeline_states(&self) -> Vec<(Stage, &str)> {
+        [
+            (Stage::Peo, &self.peo_state),
+            (Stage::Org, &self.org_state),
+            (Stage::Ori, &self.ori_state),
+            (Stage::By, &self.by_state),
+            (Stage::PeoExpression, &self.peo_expression_state),
+            (Stage::HeadlineClassification, &self.headline_classification_state),
+            (Stage::Keywords, &self.keywords_state),
+            (Stage::Abstract, &self.abstract_state),
+            (Stage::TopicSentence, &self.topic_sentence_state),
+            (Stage::TopicSentenceClassification, &self.topic_sentence_classification_state),
+            (Stage::ExpressionClassification, &self.expression_classification_state),
+        ]
+        .into_iter()
+        .filter_map(|(stage, state)| state.as_deref().map(|s| (stage, s)))
+        .collect()
+    }
+
+    /// The `*_State` field of `stage`.
+    pub fn pipeline_state_mut(&mut self, stage: Stage) -> &mut Option<String> {
+        match stage {
+            Stage::Peo => &mut self.peo_state,
+            Stage::Org => &mut self.org_state,
+            Stage::Ori => &mut self.ori_state,
+            Stage::By => &mut self.by_state,
+            Stage::PeoExpression => &mut self.peo_expression_state,
+            Stage::HeadlineClassification => &mut self.headline_classification_state,
+            Stage::Keywords => &mut self.keywords_state,
+            Stage::Abstract => &mut self.abstract_state,
+            Stage::TopicSentence => &mut self.topic_sentence_state,
+            Stage::TopicSentenceClassification => &mut self.topic_sentence_classification_state,
+            Stage::ExpressionClassification => &mut self.expression_classification_state,
+        }
+    }
+}
+
 impl People {
-    pub fn get_identity(&self) -> Option<String> {
-        let mut valid: Vec<&str> = vec![];
-        if let Some(s) = &self.identity_entertainment {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_refugee {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_crime {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_military {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_business {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_expert {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_media {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_religion {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_activist {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_politician {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_judge {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_student {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_terrorist {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_sports {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.identity_lawyer {
-            valid.push(s.as_str())
-        };
-        if valid.is_empty() {
-            None
-        } else {
-            Some(valid.join(","))
+    pub fn identities(&self) -> Vec<(Identity, &str)> {
+        [
+            (Identity::Entertainment, &self.identity_entertainment),
+            (Identity::Refugee, &self.identity_refugee),
+            (Identity::Crime, &self.identity_crime),
+            (Identity::Military, &self.identity_military),
+            (Identity::Business, &self.identity_business),
+            (Identity::Expert, &self.identity_expert),
+            (Identity::Media, &self.identity_media),
+            (Identity::Religion, &self.identity_religion),
+            (Identity::Activist, &self.identity_activist),
+            (Identity::Politician, &self.identity_politician),
+            (Identity::Judge, &self.identity_judge),
+            (Identity::Student, &self.identity_student),
+            (Identity::Terrorist, &self.identity_terrorist),
+            (Identity::Sports, &self.identity_sports),
+            (Identity::Lawyer, &self.identity_lawyer),
+        ]
+        .into_iter()
+        .filter_map(|(identity, value)| value.as_deref().map(|s| (identity, s)))
+        .collect()
+    }
+
+    /// The `Identity_*` field of `identity`.
+    pub fn identity_mut(&mut self, identity: Identity) -> &mut Option<String> {
+        match identity {
+            Identity::Entertainment => &mut self.identity_entertainment,
+            Identity::Refugee => &mut self.identity_refugee,
+            Identity::Crime => &mut self.identity_crime,
+            Identity::Military => &mut self.identity_military,
+            Identity::Business => &mut self.identity_business,
+            Identity::Expert => &mut self.identity_expert,
+            Identity::Media => &mut self.identity_media,
+            Identity::Religion => &mut self.identity_religion,
+            Identity::Activist => &mut self.identity_activist,
+            Identity::Politician => &mut self.identity_politician,
+            Identity::Judge => &mut self.identity_judge,
+            Identity::Student => &mut self.identity_student,
+            Identity::Terrorist => &mut self.identity_terrorist,
+            Identity::Sports => &mut self.identity_sports,
+            Identity::Lawyer => &mut self.identity_lawyer,
         }
     }
 
+    pub fn country_attributes(&self) -> Attributes<'_> {
+        Attributes {
+            geography: self.geography.as_deref(),
+            orob: self.orob.as_deref().and_then(parse_orob),
+            orob_region: self.orob_region.as_deref(),
+            geopolitics: self.geopolitics.as_deref(),
+        }
+    }
+
+    /// `Name_Clean` if present, falling back to the raw `Name`.
+    pub fn canonical_name(&self) -> Option<&str> {
+        self.name_clean
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .or_else(|| self.name.as_deref().map(str::trim).filter(|s| !s.is_empty()))
+    }
+
+    pub fn accounts(&self) -> Vec<Account<'_>> {
+        let mut accounts = vec![];
+        if let Some(handle) = self.twitter_acc.as_deref().or(self.twitter.as_deref()) {
+            accounts.push(Account {
+                platform: Platform::Twitter,
+                account: twitter_handle(handle),
+                url: self.twitter.as_deref(),
+                screenshot: None,
+            })
+        };
+        if let Some(url) = &self.fb {
+            accounts.push(Account {
+                platform: Platform::Facebook,
+                account: url_account(url),
+                url: Some(url.as_str()),
+                screenshot: self.fb_shot.as_deref(),
+            })
+        };
+        if let Some(url) = &self.youtube_url {
+            accounts.push(Account {
+                platform: Platform::Youtube,
+                account: url_account(url),
+                url: Some(url.as_str()),
+                screenshot: None,
+            })
+        };
+        accounts.retain(|a| !a.account.is_empty());
+        accounts
+    }
+
+    pub fn legislator_term(&self) -> Option<Term<'_>> {
+        let period = self
+            .from_congressman_period
+            .as_deref()
+            .filter(|s| *s != "None");
+        let term = Term {
+            chamber: self.from_congressman.as_deref(),
+            state: self.from_congressman_state.as_deref(),
+            district: self.from_congressman_district.as_deref(),
+            party: self.from_congressman_party.as_deref(),
+            period,
+            start_year: None,
+            end_year: None,
+        };
+        if term.chamber.is_none()
+            && term.state.is_none()
+            && term.district.is_none()
+            && term.party.is_none()
+            && period.is_none()
+        {
+            return None;
+        }
+        let (start_year, end_year) = period.map_or((None, None), parse_period);
+        Some(Term { start_year, end_year, ..term })
+    }
+
     pub fn get_from(&self) -> Option<String> {
         let mut valid = vec![];
         if let Some(s) = &self.from_tibet {
@@ -280,29 +394,12 @@ impl People {
         if let Some(s) = &self.from_uyghur {
             valid.push(s.as_str())
         };
-        if let Some(s) = &self.from_congressman_state {
-            valid.push(s.as_str())
-        };
         if let Some(s) = &self.from_media {
             valid.push(s.as_str())
         };
-        if let Some(s) = &self.from_congressman_district {
-            valid.push(s.as_str())
-        };
         if let Some(s) = &self.from_expert {
             valid.push(s.as_str())
         };
-        if let Some(s) = &self.from_congressman {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_congressman_period {
-            if s != "None" {
-                valid.push(s.as_str())
-            }
-        };
-        if let Some(s) = &self.from_congressman_party {
-            valid.push(s.as_str())
-        };
         if let Some(s) = &self.from_topic {
             valid.push(s.as_str())
         };
@@ -315,69 +412,80 @@ impl People {
 }
 
 impl Source {
-    pub(crate) fn get_from(&self) -> Option<String> {
-        let mut valid: Vec<&str> = vec![];
-        if let Some(s) = &self.from_blog {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_bank {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_department {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_journal {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_institution {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_senator {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_department_country {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_embassy_country {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_embassy_located {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_facebook {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_news_agency {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_organization {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_representatives {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_representatives_region {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_senator_region {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_twitter {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_university {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_university_region {
-            valid.push(s.as_str())
-        };
-        if let Some(s) = &self.from_web {
-            valid.push(s.as_str())
-        };
-        if valid.is_empty() {
-            None
-        } else {
-            Some(valid.join(","))
+    pub fn country_attributes(&self) -> Attributes<'_> {
+        Attributes {
+            geography: self.geography.as_deref(),
+            orob: self.orob.as_deref().and_then(parse_orob),
+            orob_region: self.orob_region.as_deref(),
+            geopolitics: self.geopolitics.as_deref(),
+        }
+    }
+
+    pub fn affiliations(&self) -> Vec<(Affiliation, &str)> {
+        [
+            (Affiliation::Blog, &self.from_blog),
+            (Affiliation::Bank, &self.from_bank),
+            (Affiliation::Department, &self.from_department),
+            (Affiliation::Journal, &self.from_journal),
+            (Affiliation::Institution, &self.from_institution),
+            (Affiliation::Senator, &self.from_senator),
+            (Affiliation::DepartmentCountry, &self.from_department_country),
+            (Affiliation::EmbassyCountry, &self.from_embassy_country),
+            (Affiliation::EmbassyLocated, &self.from_embassy_located),
+            (Affiliation::Facebook, &self.from_facebook),
+            (Affiliation::NewsAgency, &self.from_news_agency),
+            (Affiliation::Organization, &self.from_organization),
+            (Affiliation::Representatives, &self.from_representatives),
+            (Affiliation::RepresentativesRegion, &self.from_representatives_region),
+            (Affiliation::SenatorRegion, &self.from_senator_region),
+            (Affiliation::Twitter, &self.from_twitter),
+            (Affiliation::University, &self.from_university),
+            (Affiliation::UniversityRegion, &self.from_university_region),
+            (Affiliation::UniversityNews, &self.from_university_news),
+            (Affiliation::Web, &self.from_web),
+        ]
+        .into_iter()
+        .filter_map(|(affiliation, value)| value.as_deref().map(|s| (affiliation, s)))
+        .collect()
+    }
+
+    /// The `From_*` field of `affiliation`.
+    pub fn affiliation_mut(&mut self, affiliation: Affiliation) -> &mut Option<String> {
+        match affiliation {
+            Affiliation::Blog => &mut self.from_blog,
+            Affiliation::Bank => &mut self.from_bank,
+            Affiliation::Department => &mut self.from_department,
+            Affiliation::Journal => &mut self.from_journal,
+            Affiliation::Institution => &mut self.from_institution,
+            Affiliation::Senator => &mut self.from_senator,
+            Affiliation::DepartmentCountry => &mut self.from_department_country,
+            Affiliation::EmbassyCountry => &mut self.from_embassy_country,
+            Affiliation::EmbassyLocated => &mut self.from_embassy_located,
+            Affiliation::Facebook => &mut self.from_facebook,
+            Affiliation::NewsAgency => &mut self.from_news_agency,
+            Affiliation::Organization => &mut self.from_organization,
+            Affiliation::Representatives => &mut self.from_representatives,
+            Affiliation::RepresentativesRegion => &mut self.from_representatives_region,
+            Affiliation::SenatorRegion => &mut self.from_senator_region,
+            Affiliation::Twitter => &mut self.from_twitter,
+            Affiliation::University => &mut self.from_university,
+            Affiliation::UniversityRegion => &mut self.from_university_region,
+            Affiliation::UniversityNews => &mut self.from_university_news,
+            Affiliation::Web => &mut self.from_web,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opinions_without_score_or_span_round_trip() {
+        let opinion: Opinion = serde_json::from_str(r#"{"text": "old opinion"}"#).unwrap();
+        assert_eq!((opinion.score, opinion.start, opinion.end), (None, None, None));
+        assert_eq!(serde_json::to_string(&opinion).unwrap(), r#"{"text":"old opinion"}"#);
+        let opinion: Opinion = serde_json::from_str(r#"{"score": 0.0, "start": 0, "end": 0, "text": "x"}"#).unwrap();
+        assert_eq!((opinion.score, opinion.start, opinion.end), (Some(0.0), Some(0), Some(0)));
+    }
+}
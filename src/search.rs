@@ -0,0 +1,112 @@
+use sqlx::{Pool, Postgres, Row};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Read `TEXT_SEARCH_CONFIG`, defaulting to `english`.
+pub fn config_from_env() -> String {
+    std::env::var("TEXT_SEARCH_CONFIG").unwrap_or_else(|_| "english".to_string())
+}
+
+/// Add the generated `search` columns and their GIN indexes to `article` and
+/// `opinion`, rebuilding them when the text search configuration changed.
+pub async fn create_indexes(pool: &Pool<Postgres>, config: &str) -> std::result::Result<(), sqlx::Error> {
+    // Fails on unknown configurations and gives the schema qualified name used in the expressions
+    let config: String = sqlx::query("SELECT $1::regconfig::oid::regconfig::text AS config")
+        .bind(config)
+        .fetch_one(pool)
+        .await?
+        .get("config");
+    let columns = [
+        (
+            "article",
+            "setweight(to_tsvector('{c}'::regconfig, coalesce(title, '')), 'A') || \
+            setweight(to_tsvector('{c}'::regconfig, coalesce(abstract, '')), 'B') || \
+            setweight(to_tsvector('{c}'::regconfig, coalesce(body, '')), 'C')",
+        ),
+        ("opinion", "to_tsvector('{c}'::regconfig, text)"),
+    ];
+    for (table, expression) in columns {
+        let expression = expression.replace("{c}", &config);
+        let current: Option<String> = sqlx::query(
+            "SELECT generation_expression FROM information_schema.columns \
+            WHERE table_schema = current_schema() AND table_name = $1 AND column_name = 'search'",
+        )
+        .bind(table)
+        .fetch_optional(pool)
+        .await?
+        .map(|row| row.get("generation_expression"));
+        if current.is_some_and(|e| !e.contains(&format!("'{}'::regconfig", config))) {
+            log::warn!("Rebuilding {}.search with text search configuration {}", table, config);
+            sqlx::query(&format!("ALTER TABLE {} DROP COLUMN search", table))
+                .execute(pool)
+                .await?;
+        }
+        sqlx::query(&format!(
+            "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS search TSVECTOR GENERATED ALWAYS AS ({expression}) STORED"
+        ))
+        .execute(pool)
+        .await?;
+        sqlx::query(&format!("CREATE INDEX IF NOT EXISTS {table}_search ON {table} USING GIN (search)"))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Print articles and opinions matching `query`, best first, as CSV on stdout.
+///
+/// `query` uses the web search syntax: quoted phrases, `or` and `-` for negation.
+/// Article matches list the article's sources and their countries, opinion
+/// matches the author and the author's country.
+pub async fn search(pool: &Pool<Postgres>, query: &str, config: &str, limit: i64) -> Result<()> {
+    let rows = sqlx::query(
+        "WITH q AS (SELECT websearch_to_tsquery($1::regconfig, $2) AS q)
+        SELECT * FROM (
+            SELECT 'article' AS kind, ts_rank(a.search, q.q) AS rank, a.id AS article_id, a.title, a.time,
+                string_agg(DISTINCT s.name, '; ') AS author, string_agg(DISTINCT c.name, '; ') AS country,
+                ts_headline($1::regconfig, coalesce(a.abstract, a.body, a.title, ''), q.q) AS snippet
+            FROM article a
+            CROSS JOIN q
+            LEFT JOIN source_article sa ON sa.article_id = a.id
+            LEFT JOIN source s ON s.id = sa.source_id
+            LEFT JOIN country c ON c.id = s.country_id
+            WHERE a.search @@ q.q
+            GROUP BY a.id, q.q
+            UNION ALL
+            SELECT 'opinion', ts_rank(o.search, q.q), a.id, a.title, a.time, p.name, c.name,
+                ts_headline($1::regconfig, o.text, q.q)
+            FROM opinion o
+            CROSS JOIN q
+            JOIN people p ON p.id = o.author_id
+            JOIN article a ON a.id = o.article_id
+            LEFT JOIN country c ON c.id = p.country_id
+            WHERE o.search @@ q.q
+        ) hits
+        ORDER BY rank DESC, article_id
+        LIMIT $3",
+    )
+    .bind(config)
+    .bind(query)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    writer.write_record(["kind", "rank", "article_id", "title", "time", "author", "country", "snippet"])?;
+    for row in rows {
+        writer.write_record([
+            row.get::<String, _>("kind"),
+            format!("{:.4}", row.get::<f32, _>("rank")),
+            row.get::<i32, _>("article_id").to_string(),
+            row.get::<Option<String>, _>("title").unwrap_or_default(),
+            row.get::<Option<chrono::DateTime<chrono::Utc>>, _>("time")
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            row.get::<Option<String>, _>("author").unwrap_or_default(),
+            row.get::<Option<String>, _>("country").unwrap_or_default(),
+            row.get::<String, _>("snippet"),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
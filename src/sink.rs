@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::country::Attributes;
+use crate::pipeline::{Stage, Status};
+use crate::schema::{Opinion, People, Root, Source};
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// A storage target for migrated records.
+///
+/// The traversal in `process_roots` parses and resolves each record and hands
+/// the pieces to every sink. Ids are only meaningful within the sink that
+/// returned them. Operations a target has no tables for default to doing nothing.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Record a country name missing from the reference data, returning whether it is new.
+    async fn record_unmatched_country(&self, _raw: &str) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Record attributes that disagree with the stored country, returning
+    /// those not seen before as `(attribute, stored, incoming)`.
+    async fn record_country_conflicts(
+        &self,
+        _name: &str,
+        _attributes: &Attributes<'_>,
+    ) -> Result<Vec<(&'static str, String, String)>> {
+        Ok(vec![])
+    }
+
+    /// Upsert the reference country called `name`.
+    async fn resolve_country(
+        &self,
+        name: &str,
+        attributes: &Attributes<'_>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<i32>;
+
+    /// Upsert the source known under the normalised `name`.
+    async fn resolve_source(&self, name: &str, country_id: Option<i32>, updated_at: Option<DateTime<Utc>>) -> Result<i32>;
+
+    /// Store the affiliations of a source.
+    async fn add_source_details(&self, _source_id: i32, _source: &Source) -> Result<()> {
+        Ok(())
+    }
+
+    /// Upsert the article identified by `key`.
+    async fn upsert_article(
+        &self,
+        key: &str,
+        root: &Root,
+        time: Option<DateTime<Utc>>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<i32>;
+
+    async fn set_pipeline_state(&self, _article_id: i32, _stage: Stage, _status: Status, _raw: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn link_source(&self, source_id: i32, article_id: i32) -> Result<()>;
+
+    /// Upsert the person with the normalised `name`, applying `person::choose`.
+    async fn resolve_person(
+        &self,
+        name: &str,
+        country_id: Option<i32>,
+        people: &People,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<i32>;
+
+    /// Store aliases, identities, accounts and the legislator term of a person.
+    async fn add_person_details(&self, _people_id: i32, _people: &People) -> Result<()> {
+        Ok(())
+    }
+
+    async fn add_opinion(&self, author_id: i32, article_id: i32, opinion: &Opinion) -> Result<()>;
+
+    /// Called once every record was processed.
+    async fn finish(&self) -> Result<()> {
+        Ok(())
+    }
+}
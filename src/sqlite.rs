@@ -0,0 +1,280 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Params};
+
+use crate::country::{Attributes, Reference};
+use crate::person::{self, Candidate};
+use crate::schema::{Opinion, People, Root};
+use crate::sink::{Result, Sink};
+use crate::upsert::Policy;
+
+/// The core tables of the Postgres schema, without the lookup and audit tables.
+const TABLES: &str = "
+CREATE TABLE IF NOT EXISTS country (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    iso_alpha2 TEXT UNIQUE,
+    iso_alpha3 TEXT UNIQUE,
+    region TEXT,
+    geography TEXT,
+    belt_and_road BOOLEAN,
+    bri_since DATE,
+    orob_region TEXT,
+    geopolitics TEXT,
+    updated_at TIMESTAMP
+);
+CREATE TABLE IF NOT EXISTS country_unmatched (
+    name TEXT PRIMARY KEY,
+    occurrences INTEGER NOT NULL DEFAULT 1,
+    first_seen TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE TABLE IF NOT EXISTS people (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    country_id INTEGER REFERENCES country(id) ON DELETE CASCADE,
+    title TEXT,
+    origin TEXT,
+    updated_at TIMESTAMP
+);
+CREATE UNIQUE INDEX IF NOT EXISTS people_name_country ON people (name, COALESCE(country_id, 0));
+CREATE TABLE IF NOT EXISTS source (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    country_id INTEGER REFERENCES country(id) ON DELETE CASCADE,
+    updated_at TIMESTAMP
+);
+CREATE TABLE IF NOT EXISTS article (
+    id INTEGER PRIMARY KEY,
+    key TEXT UNIQUE NOT NULL,
+    title TEXT,
+    time TIMESTAMP,
+    media_id TEXT,
+    original_site TEXT,
+    abstract TEXT,
+    body TEXT,
+    updated_at TIMESTAMP
+);
+CREATE TABLE IF NOT EXISTS source_article (
+    source_id INTEGER NOT NULL REFERENCES source(id) ON DELETE CASCADE,
+    article_id INTEGER NOT NULL REFERENCES article(id) ON DELETE CASCADE,
+    UNIQUE (source_id, article_id)
+);
+CREATE TABLE IF NOT EXISTS opinion (
+    id INTEGER PRIMARY KEY,
+    author_id INTEGER NOT NULL REFERENCES people(id) ON DELETE CASCADE,
+    text TEXT UNIQUE NOT NULL,
+    article_id INTEGER NOT NULL REFERENCES article(id) ON DELETE CASCADE,
+    score REAL,
+    span_start INTEGER,
+    span_end INTEGER
+);
+";
+
+pub fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(TABLES)?;
+    // Earlier versions did not keep the score and span of opinions
+    let scored: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('opinion') WHERE name = 'score')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !scored {
+        conn.execute_batch(
+            "ALTER TABLE opinion ADD COLUMN score REAL; \
+            ALTER TABLE opinion ADD COLUMN span_start INTEGER; \
+            ALTER TABLE opinion ADD COLUMN span_end INTEGER;",
+        )?;
+    }
+    let mut seed = conn.prepare(
+        "INSERT INTO country (name, iso_alpha2, iso_alpha3, region) VALUES (?1, ?2, ?3, ?4) \
+        ON CONFLICT (name) DO UPDATE SET iso_alpha2 = excluded.iso_alpha2, \
+        iso_alpha3 = excluded.iso_alpha3, region = excluded.region",
+    )?;
+    for c in Reference::bundled().countries() {
+        seed.execute(params![c.name, c.alpha2, c.alpha3, c.region])?;
+    }
+    Ok(())
+}
+
+/// Insert with `sql`, which returns the id unless the policy left the row
+/// alone, in which case it is looked up with `select`.
+fn upsert(conn: &Connection, sql: &str, values: impl Params, select: &str, key: impl Params) -> rusqlite::Result<i32> {
+    match conn.prepare_cached(sql)?.query_row(values, |row| row.get(0)).optional()? {
+        Some(id) => Ok(id),
+        None => conn.prepare_cached(select)?.query_row(key, |row| row.get(0)),
+    }
+}
+
+/// Writes records into a SQLite file inside one transaction, committed by `finish`.
+pub struct SqliteSink {
+    conn: Mutex<Connection>,
+    country_sql: String,
+    source_sql: String,
+    article_sql: String,
+    people_sql: String,
+}
+
+impl SqliteSink {
+    /// Open or create the database at `path` with the tables in place.
+    pub fn open(path: &Path) -> rusqlite::Result<SqliteSink> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        create_tables(&conn)?;
+        conn.execute_batch("BEGIN")?;
+        Ok(SqliteSink {
+            conn: Mutex::new(conn),
+            country_sql: format!(
+                "INSERT INTO country (name, geography, belt_and_road, bri_since, orob_region, geopolitics, updated_at) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) {} RETURNING id",
+                Policy::from_env("country", Policy::MergeNonNull).on_conflict(
+                    "country",
+                    "name",
+                    &["geography", "belt_and_road", "bri_since", "orob_region", "geopolitics"],
+                ),
+            ),
+            source_sql: format!(
+                "INSERT INTO source (name, country_id, updated_at) VALUES (?1, ?2, ?3) {} RETURNING id",
+                Policy::from_env("source", Policy::FirstWins).on_conflict("source", "name", &["country_id"]),
+            ),
+            article_sql: format!(
+                "INSERT INTO article (key, title, time, media_id, original_site, abstract, body, updated_at) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) {} RETURNING id",
+                Policy::from_env("article", Policy::FirstWins).on_conflict(
+                    "article",
+                    "key",
+                    &["title", "time", "media_id", "original_site", "abstract", "body"],
+                ),
+            ),
+            people_sql: format!(
+                "INSERT INTO people (name, country_id, origin, title, updated_at) VALUES (?1, ?2, ?3, ?4, ?5) {} RETURNING id",
+                Policy::from_env("people", Policy::FirstWins).on_conflict("people", "name, COALESCE(country_id, 0)", &["origin", "title"]),
+            ),
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+    async fn record_unmatched_country(&self, raw: &str) -> Result<bool> {
+        let occurrences: i32 = self.conn.lock().unwrap().prepare_cached(
+            "INSERT INTO country_unmatched (name) VALUES (?1) \
+            ON CONFLICT (name) DO UPDATE SET occurrences = occurrences + 1 \
+            RETURNING occurrences",
+        )?
+        .query_row([raw], |row| row.get(0))?;
+        Ok(occurrences == 1)
+    }
+
+    async fn resolve_country(
+        &self,
+        name: &str,
+        attributes: &Attributes<'_>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<i32> {
+        Ok(upsert(
+            &self.conn.lock().unwrap(),
+            &self.country_sql,
+            params![
+                name,
+                attributes.geography,
+                attributes.orob.map(|o| o.member),
+                attributes.orob.and_then(|o| o.since),
+                attributes.orob_region,
+                attributes.geopolitics,
+                updated_at,
+            ],
+            "SELECT id FROM country WHERE name = ?1",
+            [name],
+        )?)
+    }
+
+    async fn resolve_source(&self, name: &str, country_id: Option<i32>, updated_at: Option<DateTime<Utc>>) -> Result<i32> {
+        Ok(upsert(
+            &self.conn.lock().unwrap(),
+            &self.source_sql,
+            params![name, country_id, updated_at],
+            "SELECT id FROM source WHERE name = ?1",
+            [name],
+        )?)
+    }
+
+    async fn upsert_article(
+        &self,
+        key: &str,
+        root: &Root,
+        time: Option<DateTime<Utc>>,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<i32> {
+        Ok(upsert(
+            &self.conn.lock().unwrap(),
+            &self.article_sql,
+            params![
+                key,
+                root.headline,
+                time,
+                root.media_id,
+                root.original_site,
+                root.abstract_field,
+                root.body,
+                updated_at,
+            ],
+            "SELECT id FROM article WHERE key = ?1",
+            [key],
+        )?)
+    }
+
+    async fn link_source(&self, source_id: i32, article_id: i32) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .prepare_cached("INSERT INTO source_article (source_id, article_id) VALUES (?1, ?2) ON CONFLICT DO NOTHING")?
+            .execute([source_id, article_id])?;
+        Ok(())
+    }
+
+    async fn resolve_person(
+        &self,
+        name: &str,
+        country_id: Option<i32>,
+        people: &People,
+        updated_at: Option<DateTime<Utc>>,
+    ) -> Result<i32> {
+        let conn = self.conn.lock().unwrap();
+        let candidates = conn
+            .prepare_cached("SELECT id, name, country_id, title FROM people WHERE name = ?1")?
+            .query_map([name], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<rusqlite::Result<Vec<Candidate>>>()?;
+        let resolution = person::choose(&candidates, name, country_id, people.title.as_deref());
+        if let Some(id) = resolution.adopt {
+            conn.prepare_cached("UPDATE people SET country_id = ?2 WHERE id = ?1")?
+                .execute(params![id, resolution.country_id])?;
+        }
+        Ok(upsert(
+            &conn,
+            &self.people_sql,
+            params![resolution.name, resolution.country_id, people.get_from(), people.title, updated_at],
+            "SELECT id FROM people WHERE name = ?1 AND COALESCE(country_id, 0) = COALESCE(?2, 0)",
+            params![resolution.name, resolution.country_id],
+        )?)
+    }
+
+    async fn add_opinion(&self, author_id: i32, article_id: i32, opinion: &Opinion) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .prepare_cached(
+                "INSERT INTO opinion (author_id, text, article_id, score, span_start, span_end) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT DO NOTHING",
+            )?
+            .execute(params![author_id, opinion.text, article_id, opinion.score, opinion.start, opinion.end])?;
+        Ok(())
+    }
+
+    async fn finish(&self) -> Result<()> {
+        self.conn.lock().unwrap().execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
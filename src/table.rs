@@ -0,0 +1,176 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Column types of the exported tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Int,
+    BigInt,
+    Float,
+    Bool,
+    Text,
+    Date,
+    Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i32),
+    BigInt(i64),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+    Date(NaiveDate),
+    Timestamp(DateTime<Utc>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        *self == Value::Null
+    }
+
+    /// Whether `self` is newer than `other`, which like SQL is false when either is NULL.
+    pub fn newer_than(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b) == Ordering::Greater,
+            _ => false,
+        }
+    }
+}
+
+impl From<Option<i32>> for Value {
+    fn from(v: Option<i32>) -> Value {
+        v.map_or(Value::Null, Value::Int)
+    }
+}
+
+impl From<Option<i64>> for Value {
+    fn from(v: Option<i64>) -> Value {
+        v.map_or(Value::Null, Value::BigInt)
+    }
+}
+
+impl From<Option<f64>> for Value {
+    fn from(v: Option<f64>) -> Value {
+        v.map_or(Value::Null, Value::Float)
+    }
+}
+
+impl From<Option<bool>> for Value {
+    fn from(v: Option<bool>) -> Value {
+        v.map_or(Value::Null, Value::Bool)
+    }
+}
+
+impl From<Option<&str>> for Value {
+    fn from(v: Option<&str>) -> Value {
+        v.map_or(Value::Null, |s| Value::Text(s.to_string()))
+    }
+}
+
+impl From<Option<NaiveDate>> for Value {
+    fn from(v: Option<NaiveDate>) -> Value {
+        v.map_or(Value::Null, Value::Date)
+    }
+}
+
+impl From<Option<DateTime<Utc>>> for Value {
+    fn from(v: Option<DateTime<Utc>>) -> Value {
+        v.map_or(Value::Null, Value::Timestamp)
+    }
+}
+
+/// Rows of one table, held in memory for export.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: &'static str,
+    pub columns: &'static [(&'static str, Kind)],
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(name: &'static str) -> Table {
+        let columns = COLUMNS
+            .iter()
+            .find(|(table, _)| *table == name)
+            .unwrap_or_else(|| panic!("Unknown table: {}", name))
+            .1;
+        Table { name, columns, rows: vec![] }
+    }
+
+    pub fn column(&self, name: &str) -> usize {
+        self.columns
+            .iter()
+            .position(|(c, _)| *c == name)
+            .unwrap_or_else(|| panic!("Unknown column {}.{}", self.name, name))
+    }
+}
+
+/// The exported tables and their columns, in dependency order.
+pub const COLUMNS: &[(&str, &[(&str, Kind)])] = &[
+    (
+        "country",
+        &[
+            ("id", Kind::Int),
+            ("name", Kind::Text),
+            ("iso_alpha2", Kind::Text),
+            ("iso_alpha3", Kind::Text),
+            ("region", Kind::Text),
+            ("geography", Kind::Text),
+            ("belt_and_road", Kind::Bool),
+            ("bri_since", Kind::Date),
+            ("orob_region", Kind::Text),
+            ("geopolitics", Kind::Text),
+            ("updated_at", Kind::Timestamp),
+        ],
+    ),
+    (
+        "source",
+        &[
+            ("id", Kind::Int),
+            ("name", Kind::Text),
+            ("country_id", Kind::Int),
+            ("updated_at", Kind::Timestamp),
+        ],
+    ),
+    (
+        "people",
+        &[
+            ("id", Kind::Int),
+            ("name", Kind::Text),
+            ("country_id", Kind::Int),
+            ("title", Kind::Text),
+            ("origin", Kind::Text),
+            ("updated_at", Kind::Timestamp),
+        ],
+    ),
+    (
+        "article",
+        &[
+            ("id", Kind::Int),
+            ("key", Kind::Text),
+            ("title", Kind::Text),
+            ("time", Kind::Timestamp),
+            ("media_id", Kind::Text),
+            ("original_site", Kind::Text),
+            ("abstract", Kind::Text),
+            ("body", Kind::Text),
+            ("updated_at", Kind::Timestamp),
+        ],
+    ),
+    ("source_article", &[("source_id", Kind::Int), ("article_id", Kind::Int)]),
+    (
+        "opinion",
+        &[
+            ("id", Kind::Int),
+            ("author_id", Kind::Int),
+            ("text", Kind::Text),
+            ("article_id", Kind::Int),
+            ("score", Kind::Float),
+            ("span_start", Kind::BigInt),
+            ("span_end", Kind::BigInt),
+        ],
+    ),
+];
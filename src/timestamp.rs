@@ -0,0 +1,158 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Formats tried in order when `TIME_FORMATS` is not set.
+const DEFAULT_FORMATS: &[&str] = &[
+    "rfc3339",
+    "rfc2822",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y/%m/%d",
+    "%Y.%m.%d",
+    "%Y年%m月%d日",
+    "%B %d, %Y",
+    "%b %d, %Y",
+    "%d %B %Y",
+    "%d %b %Y",
+];
+
+/// Timezone applied to times that carry no offset of their own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Zone {
+    /// Accept an IANA name such as `Asia/Shanghai` or an offset such as `+08:00`.
+    pub fn parse(s: &str) -> Option<Zone> {
+        let s = s.trim();
+        if let Ok(tz) = s.parse::<Tz>() {
+            return Some(Zone::Named(tz));
+        }
+        let (sign, rest) = match s.strip_prefix('+') {
+            Some(rest) => (1, rest),
+            None => (-1, s.strip_prefix('-')?),
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+        FixedOffset::east_opt(sign * seconds).map(Zone::Fixed)
+    }
+
+    fn localize(&self, time: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::Fixed(offset) => offset.from_local_datetime(time).earliest().map(|t| t.with_timezone(&Utc)),
+            Zone::Named(tz) => tz.from_local_datetime(time).earliest().map(|t| t.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// Parses record times with a list of accepted formats.
+#[derive(Debug, Clone)]
+pub struct TimeParser {
+    pub formats: Vec<String>,
+    pub zone: Zone,
+}
+
+impl TimeParser {
+    /// Read `TIME_FORMATS` as a `;` separated list of chrono formats, where
+    /// `rfc3339` and `rfc2822` name the standards, and `TIME_ZONE`, defaulting to UTC.
+    pub fn from_env() -> TimeParser {
+        let formats = match std::env::var("TIME_FORMATS") {
+            Ok(formats) => formats
+                .split(';')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => DEFAULT_FORMATS.iter().map(|f| f.to_string()).collect(),
+        };
+        let zone = std::env::var("TIME_ZONE")
+            .map(|s| Zone::parse(&s).unwrap_or_else(|| panic!("Invalid TIME_ZONE: {}", s)))
+            .unwrap_or(Zone::Named(Tz::UTC));
+        TimeParser { formats, zone }
+    }
+
+    pub fn parse(&self, raw: &str) -> Option<DateTime<Utc>> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return None;
+        }
+        self.formats.iter().find_map(|format| self.parse_with(raw, format))
+    }
+
+    fn parse_with(&self, raw: &str, format: &str) -> Option<DateTime<Utc>> {
+        match format {
+            "rfc3339" => DateTime::parse_from_rfc3339(raw).ok().map(|t| t.with_timezone(&Utc)),
+            "rfc2822" => DateTime::parse_from_rfc2822(raw).ok().map(|t| t.with_timezone(&Utc)),
+            _ if format.contains("%z") || format.contains("%:z") => {
+                DateTime::parse_from_str(raw, format).ok().map(|t| t.with_timezone(&Utc))
+            }
+            _ => {
+                let time = NaiveDateTime::parse_from_str(raw, format)
+                    .or_else(|_| NaiveDate::parse_from_str(raw, format).map(|d| d.and_hms_opt(0, 0, 0).unwrap()))
+                    .ok()?;
+                self.zone.localize(&time)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(zone: &str) -> TimeParser {
+        TimeParser {
+            formats: DEFAULT_FORMATS.iter().map(|f| f.to_string()).collect(),
+            zone: Zone::parse(zone).unwrap(),
+        }
+    }
+
+    fn utc(s: &str) -> Option<DateTime<Utc>> {
+        Some(DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc))
+    }
+
+    #[test]
+    fn parses_the_default_formats() {
+        let parser = parser("UTC");
+        for raw in [
+            "2023-05-04T10:30:00Z",
+            "Thu, 04 May 2023 10:30:00 +0000",
+            "2023-05-04 10:30:00",
+            "2023-05-04T10:30:00",
+            "2023-05-04 10:30",
+            "2023/05/04 10:30:00",
+        ] {
+            assert_eq!(parser.parse(raw), utc("2023-05-04T10:30:00Z"), "{}", raw);
+        }
+        for raw in ["2023-05-04", "2023/05/04", "2023.05.04", "2023年05月04日", "May 04, 2023", "4 May 2023"] {
+            assert_eq!(parser.parse(raw), utc("2023-05-04T00:00:00Z"), "{}", raw);
+        }
+        assert_eq!(parser.parse(" "), None);
+        assert_eq!(parser.parse("yesterday"), None);
+    }
+
+    #[test]
+    fn applies_the_zone_only_to_times_without_an_offset() {
+        for zone in ["Asia/Shanghai", "+08:00", "+8"] {
+            let parser = parser(zone);
+            assert_eq!(parser.parse("2023-05-04 08:00:00"), utc("2023-05-04T00:00:00Z"), "{}", zone);
+            assert_eq!(parser.parse("2023-05-04T08:00:00Z"), utc("2023-05-04T08:00:00Z"), "{}", zone);
+        }
+        // Daylight saving time
+        let parser = parser("America/New_York");
+        assert_eq!(parser.parse("2023-01-15 12:00"), utc("2023-01-15T17:00:00Z"));
+        assert_eq!(parser.parse("2023-07-15 12:00"), utc("2023-07-15T16:00:00Z"));
+    }
+
+    #[test]
+    fn zones_reject_garbage() {
+        assert_eq!(Zone::parse("-05:30"), FixedOffset::west_opt(5 * 3600 + 30 * 60).map(Zone::Fixed));
+        assert_eq!(Zone::parse("Mars/Olympus"), None);
+        assert_eq!(Zone::parse("+99"), None);
+    }
+}
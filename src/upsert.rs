@@ -0,0 +1,206 @@
+use crate::table::Value;
+
+/// How an insert resolves a conflict on a table's natural key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Keep the row that was inserted first.
+    FirstWins,
+    /// Overwrite the row with the values of the latest record.
+    LastWins,
+    /// Only fill columns that are still NULL.
+    MergeNonNull,
+    /// Take the values of the record with the newest `Update_Time`.
+    Newest,
+}
+
+impl Policy {
+    pub fn parse(s: &str) -> Option<Policy> {
+        match s.trim().to_lowercase().as_str() {
+            "first" | "first-wins" => Some(Policy::FirstWins),
+            "last" | "last-wins" => Some(Policy::LastWins),
+            "merge" | "merge-non-null" => Some(Policy::MergeNonNull),
+            "newest" | "newest-by-update-time" => Some(Policy::Newest),
+            _ => None,
+        }
+    }
+
+    /// Read `UPSERT_POLICY_<TABLE>`, then `UPSERT_POLICY`, falling back to `default`.
+    pub fn from_env(table: &str, default: Policy) -> Policy {
+        std::env::var(format!("UPSERT_POLICY_{}", table.to_uppercase()))
+            .or_else(|_| std::env::var("UPSERT_POLICY"))
+            .map(|s| Policy::parse(&s).unwrap_or_else(|| panic!("Invalid upsert policy for {}: {}", table, s)))
+            .unwrap_or(default)
+    }
+
+    /// Build the `ON CONFLICT` clause for `table`, which must have an `updated_at` column.
+    /// The clause is understood by both Postgres and SQLite.
+    pub fn on_conflict(&self, table: &str, target: &str, columns: &[&str]) -> String {
+        let set = |f: &dyn Fn(&str) -> String| {
+            columns
+                .iter()
+                .map(|c| format!("{} = {}", c, f(c)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Policy::FirstWins => format!("ON CONFLICT ({}) DO NOTHING", target),
+            Policy::LastWins => format!(
+                "ON CONFLICT ({}) DO UPDATE SET {}, updated_at = EXCLUDED.updated_at",
+                target,
+                set(&|c| format!("EXCLUDED.{}", c)),
+            ),
+            Policy::MergeNonNull => format!(
+                "ON CONFLICT ({}) DO UPDATE SET {}, updated_at = CASE \
+                WHEN {t}.updated_at IS NULL OR EXCLUDED.updated_at > {t}.updated_at \
+                THEN EXCLUDED.updated_at ELSE {t}.updated_at END",
+                target,
+                set(&|c| format!("COALESCE({}.{}, EXCLUDED.{})", table, c, c)),
+                t = table,
+            ),
+            Policy::Newest => format!(
+                "ON CONFLICT ({}) DO UPDATE SET {}, updated_at = EXCLUDED.updated_at \
+                WHERE {t}.updated_at IS NULL OR EXCLUDED.updated_at > {t}.updated_at",
+                target,
+                set(&|c| format!("EXCLUDED.{}", c)),
+                t = table,
+            ),
+        }
+    }
+
+    /// Build the MySQL `ON DUPLICATE KEY UPDATE` clause equivalent to [`Policy::on_conflict`].
+    ///
+    /// The clause always sets `id = LAST_INSERT_ID(id)` so the insert reports
+    /// the id of the existing row. MySQL applies the assignments in order, so
+    /// `updated_at` comes last for the comparisons to see the stored value.
+    pub fn on_duplicate_key(&self, columns: &[&str]) -> String {
+        let newer = "updated_at IS NULL OR VALUES(updated_at) > updated_at";
+        let mut set = vec!["id = LAST_INSERT_ID(id)".to_string()];
+        match self {
+            Policy::FirstWins => {}
+            Policy::LastWins => {
+                set.extend(columns.iter().map(|c| format!("{c} = VALUES({c})")));
+                set.push("updated_at = VALUES(updated_at)".to_string());
+            }
+            Policy::MergeNonNull => {
+                set.extend(columns.iter().map(|c| format!("{c} = COALESCE({c}, VALUES({c}))")));
+                set.push(format!("updated_at = IF({newer}, VALUES(updated_at), updated_at)"));
+            }
+            Policy::Newest => {
+                set.extend(columns.iter().map(|c| format!("{c} = IF({newer}, VALUES({c}), {c})")));
+                set.push(format!("updated_at = IF({newer}, VALUES(updated_at), updated_at)"));
+            }
+        }
+        format!("ON DUPLICATE KEY UPDATE {}", set.join(", "))
+    }
+
+    /// Apply the policy to a row held in memory, as the SQL upserts do in a database.
+    /// `columns` are the indexes of the updatable columns besides `updated_at`.
+    pub fn merge(&self, stored: &mut [Value], incoming: &[Value], columns: &[usize], updated_at: usize) {
+        let newer = stored[updated_at].is_null() || incoming[updated_at].newer_than(&stored[updated_at]);
+        match self {
+            Policy::FirstWins => return,
+            Policy::LastWins => {}
+            Policy::MergeNonNull => {
+                for &c in columns {
+                    if stored[c].is_null() {
+                        stored[c] = incoming[c].clone();
+                    }
+                }
+                if newer {
+                    stored[updated_at] = incoming[updated_at].clone();
+                }
+                return;
+            }
+            Policy::Newest if !newer => return,
+            Policy::Newest => {}
+        }
+        for &c in columns.iter().chain([&updated_at]) {
+            stored[c] = incoming[c].clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rusqlite::Connection;
+
+    use super::*;
+
+    const POLICIES: [Policy; 4] = [Policy::FirstWins, Policy::LastWins, Policy::MergeNonNull, Policy::Newest];
+
+    fn at(seconds: i64) -> Value {
+        Value::Timestamp(Utc.timestamp_opt(seconds, 0).unwrap())
+    }
+
+    /// A stored row with a NULL column, merged with an older record setting both columns.
+    fn merged(policy: Policy, incoming_at: Value) -> Vec<Value> {
+        let mut stored = vec![Value::Text("stored".to_string()), Value::Null, at(100)];
+        let incoming = [Value::Text("incoming".to_string()), Value::Int(1), incoming_at];
+        policy.merge(&mut stored, &incoming, &[0, 1], 2);
+        stored
+    }
+
+    #[test]
+    fn parses_short_and_long_names() {
+        for (short, long, policy) in [
+            ("first", "first-wins", Policy::FirstWins),
+            ("last", "last-wins", Policy::LastWins),
+            ("merge", "merge-non-null", Policy::MergeNonNull),
+            ("newest", "newest-by-update-time", Policy::Newest),
+        ] {
+            assert_eq!(Policy::parse(short), Some(policy));
+            assert_eq!(Policy::parse(&format!(" {} ", long.to_uppercase())), Some(policy));
+        }
+        assert_eq!(Policy::parse("oldest"), None);
+    }
+
+    #[test]
+    fn merge_applies_each_policy() {
+        let text = |s: &str| Value::Text(s.to_string());
+        assert_eq!(merged(Policy::FirstWins, at(50)), [text("stored"), Value::Null, at(100)]);
+        assert_eq!(merged(Policy::LastWins, at(50)), [text("incoming"), Value::Int(1), at(50)]);
+        assert_eq!(merged(Policy::MergeNonNull, at(50)), [text("stored"), Value::Int(1), at(100)]);
+        assert_eq!(merged(Policy::MergeNonNull, at(150)), [text("stored"), Value::Int(1), at(150)]);
+        assert_eq!(merged(Policy::Newest, at(50)), [text("stored"), Value::Null, at(100)]);
+        assert_eq!(merged(Policy::Newest, at(150)), [text("incoming"), Value::Int(1), at(150)]);
+    }
+
+    /// The SQL clauses leave a database row as `merge` leaves a row in memory.
+    #[test]
+    fn on_conflict_matches_merge() {
+        for policy in POLICIES {
+            for incoming_at in [50, 150] {
+                let conn = Connection::open_in_memory().unwrap();
+                conn.execute_batch(
+                    "CREATE TABLE t (name TEXT UNIQUE, a TEXT, b INTEGER, updated_at INTEGER); \
+                    INSERT INTO t VALUES ('n', 'stored', NULL, 100);",
+                )
+                .unwrap();
+                let sql = format!(
+                    "INSERT INTO t VALUES ('n', 'incoming', 1, ?1) {}",
+                    policy.on_conflict("t", "name", &["a", "b"])
+                );
+                conn.execute(&sql, [incoming_at]).unwrap();
+                let row: (String, Option<i32>, i64) = conn
+                    .query_row("SELECT a, b, updated_at FROM t", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+                    .unwrap();
+                let expected = merged(policy, at(incoming_at));
+                assert_eq!(Value::Text(row.0), expected[0], "{:?} at {}", policy, incoming_at);
+                assert_eq!(Value::from(row.1), expected[1], "{:?} at {}", policy, incoming_at);
+                assert_eq!(at(row.2), expected[2], "{:?} at {}", policy, incoming_at);
+            }
+        }
+    }
+
+    #[test]
+    fn on_duplicate_key_reports_the_existing_id_and_sets_updated_at_last() {
+        for policy in POLICIES {
+            let clause = policy.on_duplicate_key(&["a", "b"]);
+            assert!(clause.starts_with("ON DUPLICATE KEY UPDATE id = LAST_INSERT_ID(id)"), "{}", clause);
+            if policy != Policy::FirstWins {
+                assert!(clause.rfind("updated_at =") > clause.rfind("b ="), "{}", clause);
+            }
+        }
+    }
+}